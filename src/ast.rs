@@ -4,7 +4,11 @@ use std::cell::RefCell;
 use std::fmt::Display;
 use std::rc::Rc;
 
+use crate::chunk::Closure;
+use crate::dict::Dict;
 use crate::env::Environment;
+use crate::error::Error;
+use crate::ops;
 use crate::token::Token;
 
 fn vec_to_str<T: Display>(v: &Vec<T>) -> String {
@@ -22,58 +26,281 @@ fn vec_to_str<T: Display>(v: &Vec<T>) -> String {
     s
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug)]
+pub struct Function {
+    // letで定義した関数の名前（スタックトレースに使う）
+    pub name: Option<String>,
+    pub parameters: Vec<Token>,
+    pub statements: Vec<Stmt>,
+    pub env: Rc<RefCell<Environment>>,
+}
+
+// 関数は同一のオブジェクトの場合だけ等しいとする
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, String>;
+
+// Rustで実装された関数
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub func: Box<NativeFn>,
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Native({}/{})", self.name, self.arity)
+    }
+}
+
+impl PartialEq for Native {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+#[derive(Debug)]
+pub struct Module {
+    pub path: String,
+    pub env: Rc<RefCell<Environment>>,
+    pub exports: Vec<String>,
+}
+
+// モジュールも同一のオブジェクトの場合だけ等しいとする
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Value {
-    Function(Vec<Token>, Vec<Stmt>, Rc<RefCell<Environment>>),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Dict(Rc<RefCell<Dict>>),
+    Function(Rc<Function>),
+    // VMの関数
+    Closure(Rc<Closure>),
+    Module(Rc<Module>),
+    Native(Rc<Native>),
+    // catchで捕まえた組み込みのエラー
+    Error(Rc<Error>),
+    Int(i64),
+    // 小数
     Number(f64),
+    String(String),
     Bool(bool),
     Null,
     Undefined,
 }
 
+impl Value {
+    pub fn native(
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) -> Value {
+        Value::Native(Rc::new(Native {
+            name: name.to_string(),
+            arity,
+            func: Box::new(func),
+        }))
+    }
+
+    // スクリプトで作る配列と同じくGCに登録する（ホストアプリケーションから渡す配列用）
+    pub fn array(elements: Vec<Value>) -> Value {
+        ops::array(elements)
+    }
+}
+
+// 整数と小数は数として等しければ等しいとする（1 == 1.0）
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        equal(self, other, &mut vec![])
+    }
+}
+
+// 比較中の配列や辞書の組（自分自身を含むものを無限に辿らないようにする）
+type Comparing = Vec<(*const (), *const ())>;
+
+fn equal(left: &Value, right: &Value, comparing: &mut Comparing) -> bool {
+    match (left, right) {
+        (Value::Array(l), Value::Array(r)) => {
+            containers((address(l), address(r)), comparing, |comparing| {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| equal(l, r, comparing))
+            })
+        }
+        (Value::Dict(l), Value::Dict(r)) => {
+            containers((address(l), address(r)), comparing, |comparing| {
+                l.borrow().eq_by(&r.borrow(), |l, r| equal(l, r, comparing))
+            })
+        }
+        (Value::Function(l), Value::Function(r)) => l == r,
+        (Value::Closure(l), Value::Closure(r)) => l == r,
+        (Value::Module(l), Value::Module(r)) => l == r,
+        (Value::Native(l), Value::Native(r)) => l == r,
+        (Value::Error(l), Value::Error(r)) => l == r,
+        (Value::Int(l), Value::Int(r)) => l == r,
+        (Value::Number(l), Value::Number(r)) => l == r,
+        (Value::Int(i), n @ Value::Number(_)) | (n @ Value::Number(_), Value::Int(i)) => {
+            ops::exact_int(n) == Some(*i)
+        }
+        (Value::String(l), Value::String(r)) => l == r,
+        (Value::Bool(l), Value::Bool(r)) => l == r,
+        (Value::Null, Value::Null) | (Value::Undefined, Value::Undefined) => true,
+        _ => false,
+    }
+}
+
+// 同じものどうしか、比較中の組に戻ってきたら等しいとする
+fn containers(
+    pair: (*const (), *const ()),
+    comparing: &mut Comparing,
+    f: impl FnOnce(&mut Comparing) -> bool,
+) -> bool {
+    if pair.0 == pair.1 || comparing.contains(&pair) {
+        return true;
+    }
+    comparing.push(pair);
+    let result = f(comparing);
+    comparing.pop();
+    result
+}
+
+fn address<T>(rc: &Rc<T>) -> *const () {
+    Rc::as_ptr(rc).cast()
+}
+
 // printで出力するフォーマット
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Function(parameters, _, _) => {
-                let names = parameters.iter().map(|p| &p.lexeme).collect();
-                write!(f, "(function {})", vec_to_str(&names))
-            }
-            Value::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
-            Value::Number(n) => write!(f, "{}", n),
-            Value::Null => write!(f, "null"),
-            Value::Undefined => write!(f, "undefined"),
+        write_value(f, self, &mut vec![])
+    }
+}
+
+// 出力中の配列や辞書にまた出会ったら[...]や{...}とする
+fn write_value(
+    f: &mut std::fmt::Formatter<'_>,
+    val: &Value,
+    writing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match val {
+        Value::Array(elements) if writing.contains(&address(elements)) => write!(f, "[...]"),
+        Value::Array(elements) => {
+            writing.push(address(elements));
+            write!(f, "[")?;
+            for (i, e) in elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_element(f, e, writing)?;
+            }
+            writing.pop();
+            write!(f, "]")
         }
+        Value::Dict(dict) if writing.contains(&address(dict)) => write!(f, "{{...}}"),
+        Value::Dict(dict) => {
+            writing.push(address(dict));
+            write!(f, "{{")?;
+            for (i, (k, v)) in dict.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{:?}: ", k)?;
+                write_element(f, v, writing)?;
+            }
+            writing.pop();
+            write!(f, "}}")
+        }
+        Value::Function(func) => {
+            let names = func.parameters.iter().map(|p| &p.lexeme).collect();
+            write!(f, "(function {})", vec_to_str(&names))
+        }
+        Value::Closure(closure) => {
+            let names = closure.proto.parameters.iter().map(|p| &p.lexeme).collect();
+            write!(f, "(function {})", vec_to_str(&names))
+        }
+        Value::Module(module) => write!(f, "(module {})", module.path),
+        Value::Native(native) => write!(f, "(native {})", native.name),
+        Value::Error(error) => write!(f, "(error {})", error.msg),
+        Value::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
+        Value::Int(n) => write!(f, "{}", n),
+        // 整数と区別できるように、整数値の小数には".0"をつける
+        Value::Number(n) if n.is_finite() && n.fract() == 0.0 => write!(f, "{:.1}", n),
+        Value::Number(n) => write!(f, "{}", n),
+        Value::String(s) => write!(f, "{}", s),
+        Value::Null => write!(f, "null"),
+        Value::Undefined => write!(f, "undefined"),
+    }
+}
+
+// 配列や辞書の中の文字列は引用符をつけて出力する
+fn write_element(
+    f: &mut std::fmt::Formatter<'_>,
+    val: &Value,
+    writing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match val {
+        Value::String(s) => write!(f, "{:?}", s),
+        _ => write_value(f, val, writing),
     }
 }
 
+// 変数が何個外側の環境で宣言されているか（Resolverが埋める、グローバル変数はNone）
+pub type Depth = Option<usize>;
+
 // TODO: 全部にTokenを持たせる（Runtime Errorを出すときに必要）
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
-    Assignment(Token, Box<Expr>),
+    Array(Token, Vec<Expr>),
+    Assignment(Token, Box<Expr>, Depth),
     Binary(Token, Box<Expr>, Box<Expr>),
     Call(Token, Box<Expr>, Vec<Expr>),
+    Dict(Token, Vec<(String, Expr)>),
     Function(Vec<Token>, Vec<Stmt>),
     Grouping(Box<Expr>),
+    Index(Token, Box<Expr>, Box<Expr>),
+    IndexAssignment(Token, Box<Expr>, Box<Expr>, Box<Expr>),
     Literal(Value),
     Logical(Token, Box<Expr>, Box<Expr>),
     Ternary(Token, Box<Expr>, Box<Expr>, Box<Expr>),
     Unary(Token, Box<Expr>),
-    Variable(Token),
+    // 複合代入と++/--（演算子、変数か添字の式、右辺、後置ならtrue）
+    // 演算子のトークンは二項演算子の種類と元の字句を持つ
+    Update(Token, Box<Expr>, Box<Expr>, bool),
+    Variable(Token, Depth),
 }
 
 // TODO: 全部 {:?} でもいいか？テストはどう書ける？
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expr::Assignment(name, expr) => {
+            Expr::Array(_token, elements) => {
+                write!(f, "(array")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Assignment(name, expr, _) => {
                 write!(f, "(assignment {} {})", name.lexeme, expr)
             }
             Expr::Binary(op, left, right) => {
                 write!(f, "({} {} {})", op.lexeme, left, right)
             }
             Expr::Call(_token, callee, args) => {
-                write!(f, "(call {} {}", callee, vec_to_str(args))
+                write!(f, "(call {} {})", callee, vec_to_str(args))
+            }
+            Expr::Dict(_token, entries) => {
+                write!(f, "(dict")?;
+                for (key, val) in entries {
+                    write!(f, " ({:?} {})", key, val)?;
+                }
+                write!(f, ")")
             }
             Expr::Function(parameters, statements) => {
                 write!(f, "(function (parameters")?;
@@ -87,6 +314,11 @@ impl std::fmt::Display for Expr {
                 write!(f, "))")
             }
             Expr::Grouping(expr) => write!(f, "(group {})", expr),
+            Expr::Index(_token, array, index) => write!(f, "(index {} {})", array, index),
+            Expr::IndexAssignment(_token, array, index, expr) => {
+                write!(f, "(assignment (index {} {}) {})", array, index, expr)
+            }
+            Expr::Literal(Value::String(s)) => write!(f, "{:?}", s),
             Expr::Literal(val) => write!(f, "{}", val),
             Expr::Logical(op, left, right) => {
                 write!(f, "({} {} {})", op.lexeme, left, right)
@@ -94,8 +326,13 @@ impl std::fmt::Display for Expr {
             Expr::Ternary(op, first, second, third) => {
                 write!(f, "({} {} {} {})", op.lexeme, first, second, third)
             }
-            Expr::Variable(name) => write!(f, "(var {})", name.lexeme),
+            Expr::Variable(name, _) => write!(f, "(var {})", name.lexeme),
             Expr::Unary(op, right) => write!(f, "({} {})", op.lexeme, right),
+            Expr::Update(op, target, expr, postfix) => match (op.lexeme.as_str(), postfix) {
+                ("++" | "--", false) => write!(f, "(pre{} {})", op.lexeme, target),
+                ("++" | "--", true) => write!(f, "(post{} {})", op.lexeme, target),
+                _ => write!(f, "({} {} {})", op.lexeme, target, expr),
+            },
         }
     }
 }
@@ -105,11 +342,32 @@ impl std::fmt::Display for Expr {
 pub enum Stmt {
     Block(Vec<Stmt>),
     Break(Token, Option<Box<Expr>>),
+    Continue(Token),
+    Export(Box<Stmt>),
     Expression(Box<Expr>),
+    // 初期化、条件、更新、本体
+    For(
+        Option<Box<Stmt>>,
+        Option<Box<Expr>>,
+        Option<Box<Expr>>,
+        Box<Stmt>,
+    ),
+    // 変数、inのトークン、回す値、本体
+    ForIn(Token, Token, Box<Expr>, Box<Stmt>),
     If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
-    Let(Token, Box<Expr>),
+    Import(Token, String, Token),
+    // 変数、初期値、ドキュメントコメント
+    Let(Token, Box<Expr>, Option<String>),
     Print(Box<Expr>),
-    Return(Option<Box<Expr>>),
+    Return(Token, Option<Box<Expr>>),
+    Throw(Token, Box<Expr>),
+    // 本体、catch節（変数と本体）、finally節
+    Try(
+        Token,
+        Box<Stmt>,
+        Option<(Token, Box<Stmt>)>,
+        Option<Box<Stmt>>,
+    ),
     While(Box<Expr>, Box<Stmt>),
 }
 
@@ -127,7 +385,26 @@ impl std::fmt::Display for Stmt {
                     write!(f, "(break)")
                 }
             }
+            Stmt::Continue(_) => write!(f, "(continue)"),
+            Stmt::Export(statement) => write!(f, "(export {})", statement),
             Stmt::Expression(expr) => write!(f, "(expression {})", expr),
+            Stmt::For(init, condition, increment, statement) => {
+                write!(f, "(for")?;
+                match init {
+                    Some(init) => write!(f, " {}", init)?,
+                    None => write!(f, " ()")?,
+                }
+                for expr in [condition, increment] {
+                    match expr {
+                        Some(expr) => write!(f, " {}", expr)?,
+                        None => write!(f, " ()")?,
+                    }
+                }
+                write!(f, " {})", statement)
+            }
+            Stmt::ForIn(name, _, collection, statement) => {
+                write!(f, "(for-in {} {} {})", name.lexeme, collection, statement)
+            }
             Stmt::If(condition, consequence, alternative) => {
                 write!(
                     f,
@@ -141,19 +418,36 @@ impl std::fmt::Display for Stmt {
                     }
                 )
             }
-            Stmt::Let(name, expr) => {
+            Stmt::Import(_token, path, name) => {
+                write!(f, "(import {:?} {})", path, name.lexeme)
+            }
+            Stmt::Let(name, expr, None) => {
                 write!(f, "(let {} {})", name.lexeme, expr)
             }
+            Stmt::Let(name, expr, Some(doc)) => {
+                write!(f, "(let {} {} (doc {:?}))", name.lexeme, expr, doc)
+            }
             Stmt::Print(expr) => {
                 write!(f, "(print {})", expr)
             }
-            Stmt::Return(expr) => {
+            Stmt::Return(_, expr) => {
                 if let Some(expr) = expr {
                     write!(f, "(return {})", expr)
                 } else {
                     write!(f, "(return)")
                 }
             }
+            Stmt::Throw(_, expr) => write!(f, "(throw {})", expr),
+            Stmt::Try(_, body, catch, finally) => {
+                write!(f, "(try {}", body)?;
+                if let Some((name, handler)) = catch {
+                    write!(f, " (catch {} {})", name.lexeme, handler)?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " (finally {})", finally)?;
+                }
+                write!(f, ")")
+            }
             Stmt::While(condition, statement) => {
                 write!(f, "(while {} {})", condition, statement)
             }
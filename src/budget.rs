@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, ErrorKind};
+
+// 時刻と割り込みは最初のステップとこのステップ数ごとに調べる
+const CHECK_INTERVAL: u64 = 1024;
+
+// スクリプトの実行を打ち切る条件
+// ステップは木を辿る実行方式では文と式、VMでは命令を1つ実行するごとに数える
+#[derive(Default)]
+pub(crate) struct Budget {
+    // 1回の実行で使えるステップ数と時間
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    // 実行中のもの
+    steps: u64,
+    deadline: Option<Instant>,
+    interrupted: Arc<AtomicBool>,
+}
+
+// 別のスレッドから実行中のスクリプトを止めるためのもの
+#[derive(Debug, Clone)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    // 実行中でなければ次の実行を始める前に止める（止めた実行でフラグは戻る）
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Budget {
+    pub(crate) fn set_max_steps(&mut self, steps: Option<u64>) {
+        self.max_steps = steps;
+    }
+
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub(crate) fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            interrupted: Arc::clone(&self.interrupted),
+        }
+    }
+
+    // ホストアプリケーションから実行を始めるたびに数え直す
+    // 実行していない間の割り込みはここで受け取り、その実行を始めずに止める
+    pub(crate) fn start(&mut self) -> Result<(), Error> {
+        self.steps = 0;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        if self.interrupted.swap(false, Ordering::Relaxed) {
+            return Err(abort(ErrorKind::Interrupted, "Interrupted."));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn step(&mut self) -> Result<(), Error> {
+        self.steps += 1;
+        if self.max_steps.is_some_and(|max| self.steps > max) {
+            return Err(abort(ErrorKind::OutOfFuel, "Step limit exceeded."));
+        }
+        if self.steps != 1 && !self.steps.is_multiple_of(CHECK_INTERVAL) {
+            return Ok(());
+        }
+        if self.interrupted.swap(false, Ordering::Relaxed) {
+            return Err(abort(ErrorKind::Interrupted, "Interrupted."));
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(abort(ErrorKind::Timeout, "Timed out."));
+        }
+        Ok(())
+    }
+}
+
+// どこで打ち切ったかは実行方式によって違うので、位置は持たせない
+fn abort(kind: ErrorKind, msg: &str) -> Error {
+    Error::new(0, "", msg).with_kind(kind)
+}
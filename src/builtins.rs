@@ -0,0 +1,35 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::Value;
+use crate::env::Environment;
+
+pub fn define(globals: &mut Environment) {
+    globals.set("clock", Value::native("clock", 0, clock));
+    globals.set("len", Value::native("len", 1, len));
+    globals.set("sqrt", Value::native("sqrt", 1, sqrt));
+}
+
+// UNIXエポックからの秒数
+fn clock(_args: &[Value]) -> Result<Value, String> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+    Ok(Value::Number(elapsed.as_secs_f64()))
+}
+
+fn len(args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Array(elements) => Ok(Value::Int(elements.borrow().len() as i64)),
+        Value::Dict(dict) => Ok(Value::Int(dict.borrow().len() as i64)),
+        Value::String(s) => Ok(Value::Int(s.chars().count() as i64)),
+        _ => Err("Argument must be an array, a dictionary or a string.".to_string()),
+    }
+}
+
+fn sqrt(args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Int(n) => Ok(Value::Number((*n as f64).sqrt())),
+        Value::Number(n) => Ok(Value::Number(n.sqrt())),
+        _ => Err("Argument must be a number.".to_string()),
+    }
+}
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::ast::Value;
+use crate::env::Environment;
+use crate::error::Error;
+use crate::token::{Span, Token};
+
+// VMの命令（オペランドは定数表やスタックの位置）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(u16),
+    Null,
+    True,
+    False,
+    Pop,
+    // 上からn番目（0が一番上）の値を複製して積む
+    Dup(u8),
+    // 上からn個のローカル変数を捨てる
+    PopLocals(u16),
+    // n番目から上のローカル変数を指すアップバリューを閉じる
+    CloseUpvalues(u16),
+    // 一番上の値を残して、その下のn個のローカル変数を捨てる
+    PopUnder(u16),
+    GetLocal(u16),
+    SetLocal(u16),
+    // ifやループの本体に直接書いた宣言（すでに値があればエラー）
+    DefineLocal(u16),
+    GetUpvalue(u16),
+    SetUpvalue(u16),
+    // 変数名は定数表の文字列
+    DefineGlobal(u16),
+    GetGlobal(u16),
+    SetGlobal(u16),
+    Export(u16),
+    Import(u16),
+    Array(u16),
+    // キーと値の組の数
+    Dict(u16),
+    Index,
+    SetIndex,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    FloorDivide,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    In,
+    Negate,
+    Not,
+    BitNot,
+    ToBool,
+    Print,
+    // for-inで回す配列に変える
+    Iterate,
+    // n番目のローカル変数の配列とn+1番目の位置から、次の要素とtrue（なければfalse）を積む
+    Next(u16),
+    // ジャンプ先は命令の位置
+    Jump(u32),
+    JumpIfFalse(u32),
+    Call(u8),
+    Closure(u16),
+    Return,
+    // 例外のハンドラを登録する（飛び先で例外の値を受け取る）
+    Try(u32),
+    // finally節を実行してから投げ直すためのハンドラを登録する
+    TryFinally(u32),
+    EndTry,
+    Throw,
+    // TryFinallyで受け取った例外を投げ直す
+    Rethrow,
+}
+
+// コンパイルされた命令列
+#[derive(Debug)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    pub functions: Vec<Rc<Proto>>,
+    // コンパイルしたソース（エラーの位置の文字列を切り出す）
+    src: Rc<str>,
+    // 命令の位置とソース上の位置（同じ位置が続く間は最初の命令だけ）
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    offset: usize,
+    line: usize,
+    span: Span,
+}
+
+impl Chunk {
+    pub fn new(src: Rc<str>) -> Chunk {
+        Chunk {
+            code: vec![],
+            constants: vec![],
+            functions: vec![],
+            src,
+            lines: vec![],
+        }
+    }
+
+    pub fn write(&mut self, op: Op, token: &Token) -> usize {
+        if self
+            .lines
+            .last()
+            .is_none_or(|l| l.line != token.line || l.span != token.span)
+        {
+            self.lines.push(Line {
+                offset: self.code.len(),
+                line: token.line,
+                span: token.span,
+            });
+        }
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    fn find(&self, offset: usize) -> Line {
+        let i = match self.lines.binary_search_by_key(&offset, |l| l.offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        self.lines[i]
+    }
+
+    // 命令の位置の行番号
+    pub fn line(&self, offset: usize) -> usize {
+        self.find(offset).line
+    }
+
+    // 命令の位置でのエラー（空の範囲はファイルの終わり）
+    pub fn error(&self, offset: usize, msg: &str) -> Error {
+        let Line { line, span, .. } = self.find(offset);
+        let location = match self.src.get(span.start..span.end) {
+            Some("") | None => "end",
+            Some(lexeme) => lexeme,
+        };
+        Error::new(line, location, msg).with_span(span)
+    }
+}
+
+// 関数の本体をコンパイルしたもの
+#[derive(Debug)]
+pub struct Proto {
+    // letで定義した関数の名前（スタックトレースに使う）
+    pub name: Option<String>,
+    pub parameters: Vec<Token>,
+    pub chunk: Chunk,
+    pub upvalues: Vec<Capture>,
+}
+
+// クロージャが捕捉する変数（直接外側の関数のローカル変数か、そのアップバリューか）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capture {
+    Local(u16),
+    Upvalue(u16),
+}
+
+#[derive(Debug)]
+pub enum Upvalue {
+    // まだスタック上にある変数の位置
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Closure {
+    pub proto: Rc<Proto>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    // 関数が定義されたスクリプトやモジュールのトップレベルの環境
+    pub globals: Rc<RefCell<Environment>>,
+}
+
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = self.proto.parameters.iter().map(|p| &p.lexeme).collect();
+        write!(f, "Closure({:?})", names)
+    }
+}
+
+// クロージャは同一のオブジェクトの場合だけ等しいとする
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -0,0 +1,911 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::{Expr, Stmt, Value};
+use crate::chunk::{Capture, Chunk, Op, Proto};
+use crate::error::Error;
+use crate::token::{Token, TokenValue};
+
+// 文は必ず1つの値（文の値）をスタックに残すようにコンパイルする
+// ブロックや関数の値は最後の文の値になる
+
+struct Local {
+    // ループの結果を入れる隠れた変数は空文字列
+    name: String,
+    depth: usize,
+}
+
+struct Loop {
+    start: usize,
+    // ループの結果を入れるローカル変数の位置
+    result: usize,
+    // breakのジャンプ命令の位置
+    exits: Vec<usize>,
+}
+
+// 実行中のtry文（breakやreturnで抜けるときにハンドラを外してfinally節を実行する）
+struct Try {
+    finally: Option<Stmt>,
+    // tryを始めたときのループの数
+    loops: usize,
+    // ハンドラが登録されているか（finally節のないcatch節の中では外れている）
+    handler: bool,
+}
+
+// コンパイル中の関数
+struct State {
+    name: Option<String>,
+    parameters: Vec<Token>,
+    chunk: Chunk,
+    upvalues: Vec<Capture>,
+    locals: Vec<Local>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+    tries: Vec<Try>,
+    // 変数名の定数表での位置
+    names: HashMap<String, u16>,
+}
+
+impl State {
+    fn new(name: Option<String>, parameters: Vec<Token>, src: Rc<str>) -> State {
+        State {
+            name,
+            parameters,
+            chunk: Chunk::new(src),
+            upvalues: vec![],
+            locals: vec![],
+            scope_depth: 0,
+            loops: vec![],
+            tries: vec![],
+            names: HashMap::new(),
+        }
+    }
+}
+
+pub struct Compiler {
+    // 一番外側がスクリプト、最後が今コンパイルしている関数
+    states: Vec<State>,
+    // 命令に対応づけるトークン（エラーの位置になる）
+    token: Token,
+    errors: Vec<Error>,
+    // 文のスパンが指すソース
+    src: Rc<str>,
+}
+
+impl Compiler {
+    // Resolverで調べた後の文をコンパイルする
+    pub fn compile(statements: &[Stmt], src: Rc<str>) -> Result<Proto, Vec<Error>> {
+        let mut compiler = Compiler {
+            states: vec![State::new(None, vec![], Rc::clone(&src))],
+            token: Token::new(TokenValue::EOF, "".to_string(), 0),
+            errors: vec![],
+            src,
+        };
+        compiler.statements(statements);
+        compiler.emit(Op::Return);
+
+        let state = compiler.states.pop().expect("No script to compile.");
+        if compiler.errors.is_empty() {
+            Ok(Self::proto(state))
+        } else {
+            Err(compiler.errors)
+        }
+    }
+
+    fn proto(state: State) -> Proto {
+        Proto {
+            name: state.name,
+            parameters: state.parameters,
+            chunk: state.chunk,
+            upvalues: state.upvalues,
+        }
+    }
+
+    fn state(&mut self) -> &mut State {
+        self.states.last_mut().expect("No function to compile.")
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let token = self.token.clone();
+        self.state().chunk.write(op, &token)
+    }
+
+    fn at(&mut self, token: &Token) {
+        self.token = token.clone();
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        if statements.is_empty() {
+            self.emit(Op::Null);
+        }
+        for (i, statement) in statements.iter().enumerate() {
+            if i > 0 {
+                self.emit(Op::Pop);
+            }
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::Break(token, expr) => self.break_(token, expr),
+            Stmt::Continue(token) => self.continue_(token),
+            Stmt::Export(statement) => {
+                self.statement(statement);
+                if let Stmt::Let(name, _, _) = &**statement {
+                    let name = self.name(name);
+                    self.emit(Op::Export(name));
+                }
+            }
+            Stmt::Expression(expr) => self.expression(expr),
+            Stmt::For(init, condition, increment, statement) => {
+                self.for_(init, condition, increment, statement)
+            }
+            Stmt::ForIn(name, token, collection, statement) => {
+                self.for_in(name, token, collection, statement)
+            }
+            Stmt::If(condition, consequence, alternative) => {
+                self.declare_body(consequence);
+                if let Some(alt) = alternative {
+                    self.declare_body(alt);
+                }
+                self.expression(condition);
+                let jump_else = self.emit(Op::JumpIfFalse(0));
+                self.body(consequence);
+                let jump_end = self.emit(Op::Jump(0));
+                self.patch(jump_else);
+                match alternative {
+                    Some(alt) => self.body(alt),
+                    None => {
+                        self.emit(Op::Null);
+                    }
+                }
+                self.patch(jump_end);
+            }
+            Stmt::Import(token, path, name) => {
+                self.import(token, path);
+                self.define(name);
+            }
+            Stmt::Let(name, expr, _) => self.let_(name, expr),
+            Stmt::Print(expr) => {
+                self.expression(expr);
+                self.emit(Op::Print);
+                self.emit(Op::Null);
+            }
+            Stmt::Return(_, expr) => {
+                match expr {
+                    Some(expr) => self.expression(expr),
+                    None => {
+                        self.emit(Op::Null);
+                    }
+                }
+                let tries = self.state().tries.len();
+                self.with_temporaries(1, |compiler| compiler.exit_tries(0, tries));
+                self.emit(Op::Return);
+            }
+            Stmt::Throw(token, expr) => {
+                self.expression(expr);
+                self.at(token);
+                self.emit(Op::Throw);
+            }
+            Stmt::Try(token, body, catch, finally) => self.try_(token, body, catch, finally),
+            Stmt::While(condition, statement) => {
+                self.declare_body(statement);
+                self.while_(condition, statement)
+            }
+        }
+    }
+
+    fn import(&mut self, token: &Token, path: &str) {
+        self.at(token);
+        let path = self.constant(Value::String(path.to_string()));
+        self.emit(Op::Import(path));
+    }
+
+    // ifやループの本体に直接書いた宣言の変数は、インタプリタと同じく外側のスコープに入るので、
+    // 本体より前にスタックに場所を用意しておく（トップレベルではグローバル変数になる）
+    fn declare_body(&mut self, body: &Stmt) {
+        if self.state().scope_depth == 0 {
+            return;
+        }
+        if let Stmt::Import(_, _, name) | Stmt::Let(name, _, _) = body {
+            let undefined = self.constant(Value::Undefined);
+            self.emit(Op::Constant(undefined));
+            self.add_local(name);
+        }
+    }
+
+    // declare_bodyで用意した場所に値を入れる
+    fn body(&mut self, body: &Stmt) {
+        if self.state().scope_depth == 0 {
+            return self.statement(body);
+        }
+        match body {
+            Stmt::Import(token, path, name) => {
+                self.import(token, path);
+                self.define_local(name);
+            }
+            Stmt::Let(name, expr, _) => {
+                match &**expr {
+                    Expr::Function(parameters, statements) => {
+                        self.function(Some(name), parameters, statements)
+                    }
+                    _ => self.expression(expr),
+                }
+                self.define_local(name);
+            }
+            _ => self.statement(body),
+        }
+    }
+
+    fn define_local(&mut self, name: &Token) {
+        let Some(i) = Self::local(self.state(), &name.lexeme) else {
+            panic!("Local not declared before body: {}", name.lexeme);
+        };
+        self.at(name);
+        self.emit(Op::DefineLocal(i));
+        self.emit(Op::Null);
+    }
+
+    fn let_(&mut self, name: &Token, expr: &Expr) {
+        let local = self.state().scope_depth > 0;
+        match expr {
+            // 再帰呼び出しできるように、ローカルな関数は本体より先に宣言する
+            Expr::Function(parameters, statements) if local => {
+                self.add_local(name);
+                self.function(Some(name), parameters, statements);
+                self.emit(Op::Null);
+            }
+            Expr::Function(parameters, statements) => {
+                self.function(Some(name), parameters, statements);
+                self.define(name);
+            }
+            _ => {
+                self.expression(expr);
+                self.define(name);
+            }
+        }
+    }
+
+    // スタックの一番上の値で変数を定義し、文の値を残す
+    fn define(&mut self, name: &Token) {
+        if self.state().scope_depth > 0 {
+            self.add_local(name);
+        } else {
+            self.at(name);
+            let name = self.name(name);
+            self.emit(Op::DefineGlobal(name));
+        }
+        self.emit(Op::Null);
+    }
+
+    fn while_(&mut self, condition: &Expr, statement: &Stmt) {
+        self.emit(Op::Null);
+        let result = self.hidden_local();
+
+        let start = self.state().chunk.code.len();
+        self.expression(condition);
+        let exit = self.emit(Op::JumpIfFalse(0));
+        self.state().loops.push(Loop {
+            start,
+            result,
+            exits: vec![],
+        });
+
+        self.body(statement);
+        self.emit(Op::SetLocal(result as u16));
+        self.emit(Op::Pop);
+        self.emit(Op::Jump(start as u32));
+
+        self.patch(exit);
+        self.finish_loop();
+    }
+
+    // 更新と条件の順に並べ、最初だけ条件に飛ぶ
+    fn for_(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        condition: &Option<Box<Expr>>,
+        increment: &Option<Box<Expr>>,
+        statement: &Stmt,
+    ) {
+        self.begin_scope();
+        let vars = self.state().locals.len();
+        if let Some(init) = init {
+            self.statement(init);
+            self.emit(Op::Pop);
+        }
+        self.declare_body(statement);
+        self.emit(Op::Null);
+        let result = self.hidden_local();
+        let jump_condition = self.emit(Op::Jump(0));
+
+        let start = self.state().chunk.code.len();
+        // 本体で作ったクロージャが捕捉した変数は、更新の前に切り離す
+        if vars < result {
+            let vars = self.operand(vars);
+            self.emit(Op::CloseUpvalues(vars));
+        }
+        if let Some(increment) = increment {
+            self.expression(increment);
+            self.emit(Op::Pop);
+        }
+        self.patch(jump_condition);
+        let exit = condition.as_ref().map(|condition| {
+            self.expression(condition);
+            self.emit(Op::JumpIfFalse(0))
+        });
+        self.state().loops.push(Loop {
+            start,
+            result,
+            exits: vec![],
+        });
+
+        self.body(statement);
+        self.emit(Op::SetLocal(result as u16));
+        self.emit(Op::Pop);
+        self.emit(Op::Jump(start as u32));
+
+        if let Some(exit) = exit {
+            self.patch(exit);
+        }
+        self.finish_loop();
+        self.end_scope();
+    }
+
+    // 回す配列と次の位置を隠れたローカル変数に入れておく
+    fn for_in(&mut self, name: &Token, token: &Token, collection: &Expr, statement: &Stmt) {
+        self.expression(collection);
+        self.at(token);
+        self.emit(Op::Iterate);
+        self.begin_scope();
+        let array = self.hidden_local();
+        let zero = self.constant(Value::Int(0));
+        self.emit(Op::Constant(zero));
+        self.hidden_local();
+        self.emit(Op::Null);
+        let result = self.hidden_local();
+
+        let start = self.state().chunk.code.len();
+        let array = self.operand(array);
+        self.emit(Op::Next(array));
+        let exit = self.emit(Op::JumpIfFalse(0));
+        self.state().loops.push(Loop {
+            start,
+            result,
+            exits: vec![],
+        });
+
+        self.begin_scope();
+        self.add_local(name);
+        self.statement(statement);
+        self.emit(Op::SetLocal(result as u16));
+        self.end_scope();
+        self.emit(Op::Pop);
+        self.emit(Op::Jump(start as u32));
+
+        self.patch(exit);
+        self.finish_loop();
+        self.end_scope();
+    }
+
+    // breakの飛び先を今の位置にする（結果の変数はそのまま文の値になる）
+    fn finish_loop(&mut self) {
+        let finished = self.state().loops.pop().expect("No loop to finish.");
+        for exit in finished.exits {
+            self.patch(exit);
+        }
+        self.state().locals.pop();
+    }
+
+    // スタックの一番上の値を名前のないローカル変数にする
+    fn hidden_local(&mut self) -> usize {
+        let state = self.state();
+        state.locals.push(Local {
+            name: "".to_string(),
+            depth: state.scope_depth,
+        });
+        state.locals.len() - 1
+    }
+
+    // 例外が起きると、Tryで登録したハンドラがスタックを戻して例外の値を積む
+    fn try_(
+        &mut self,
+        token: &Token,
+        body: &Stmt,
+        catch: &Option<(Token, Box<Stmt>)>,
+        finally: &Option<Box<Stmt>>,
+    ) {
+        self.at(token);
+        let handler = match catch {
+            Some(_) => self.emit(Op::Try(0)),
+            None => self.emit(Op::TryFinally(0)),
+        };
+        let loops = self.state().loops.len();
+        self.state().tries.push(Try {
+            finally: finally.as_deref().cloned(),
+            loops,
+            handler: true,
+        });
+        self.statement(body);
+        self.emit(Op::EndTry);
+        let mut jumps_end = vec![self.emit(Op::Jump(0))];
+        self.patch(handler);
+
+        let mut rethrow = catch.is_none().then_some(handler);
+        if let Some((name, handler)) = catch {
+            // catch節で起きた例外もfinally節を実行してから投げ直す
+            if finally.is_some() {
+                rethrow = Some(self.emit(Op::TryFinally(0)));
+            }
+            self.state().tries.last_mut().expect("No try.").handler = rethrow.is_some();
+            self.begin_scope();
+            self.add_local(name);
+            self.statement(handler);
+            self.end_scope();
+            if rethrow.is_some() {
+                self.emit(Op::EndTry);
+            }
+            jumps_end.push(self.emit(Op::Jump(0)));
+        }
+        let finished = self.state().tries.pop().expect("No try to finish.");
+
+        if let (Some(rethrow), Some(finally)) = (rethrow, &finished.finally) {
+            if catch.is_some() {
+                self.patch(rethrow);
+            }
+            // catch節の変数の下に、投げ直す例外が積まれている
+            let slots = if catch.is_some() { 2 } else { 1 };
+            self.with_temporaries(slots, |compiler| compiler.finally(finally));
+            self.at(token);
+            self.emit(Op::Rethrow);
+        }
+        for jump in jumps_end {
+            self.patch(jump);
+        }
+        if let Some(finally) = &finished.finally {
+            self.with_temporaries(1, |compiler| compiler.finally(finally));
+        }
+    }
+
+    // finally節の値は捨てる
+    fn finally(&mut self, finally: &Stmt) {
+        self.statement(finally);
+        self.emit(Op::Pop);
+    }
+
+    // スタックの上のn個の値をローカル変数とみなしてコンパイルする
+    fn with_temporaries(&mut self, n: usize, f: impl FnOnce(&mut Compiler)) {
+        let depth = self.state().scope_depth;
+        for _ in 0..n {
+            self.state().locals.push(Local {
+                name: "".to_string(),
+                depth,
+            });
+        }
+        f(self);
+        let len = self.state().locals.len();
+        self.state().locals.truncate(len - n);
+    }
+
+    // from番目以降のtryを内側から抜ける
+    fn exit_tries(&mut self, from: usize, to: usize) {
+        for i in (from..to).rev() {
+            let try_ = &self.state().tries[i];
+            let (handler, finally) = (try_.handler, try_.finally.clone());
+            if handler {
+                self.emit(Op::EndTry);
+            }
+            if let Some(finally) = finally {
+                // finally節の中のbreakなどは、このtryより外側だけを抜ける
+                let inner = self.state().tries.split_off(i);
+                self.finally(&finally);
+                self.state().tries.extend(inner);
+            }
+        }
+    }
+
+    // 一番内側のループの中で始めたtry
+    fn loop_tries(&mut self) -> (usize, usize) {
+        let state = self.state();
+        let loops = state.loops.len();
+        let from = state.tries.iter().position(|t| t.loops >= loops);
+        (from.unwrap_or(state.tries.len()), state.tries.len())
+    }
+
+    fn break_(&mut self, token: &Token, expr: &Option<Box<Expr>>) {
+        self.at(token);
+        match expr {
+            Some(expr) => self.expression(expr),
+            None => {
+                self.emit(Op::Null);
+            }
+        }
+        let result = self.innermost_loop().result;
+        self.emit(Op::SetLocal(result as u16));
+        self.emit(Op::Pop);
+        let (from, to) = self.loop_tries();
+        self.exit_tries(from, to);
+        self.at(token);
+        self.pop_loop_locals(result);
+        let exit = self.emit(Op::Jump(0));
+        self.state()
+            .loops
+            .last_mut()
+            .expect("Break outside loop.")
+            .exits
+            .push(exit);
+    }
+
+    fn continue_(&mut self, token: &Token) {
+        self.at(token);
+        let Loop { start, result, .. } = *self.innermost_loop();
+        let (from, to) = self.loop_tries();
+        self.exit_tries(from, to);
+        self.at(token);
+        self.pop_loop_locals(result);
+        self.emit(Op::Null);
+        self.emit(Op::SetLocal(result as u16));
+        self.emit(Op::Pop);
+        self.emit(Op::Jump(start as u32));
+    }
+
+    fn innermost_loop(&mut self) -> &Loop {
+        // ループの外のbreakやcontinueはResolverがエラーにしている
+        self.state().loops.last().expect("Break outside loop.")
+    }
+
+    // ループの本体で宣言されたローカル変数を捨てる
+    fn pop_loop_locals(&mut self, result: usize) {
+        let count = self.state().locals.len() - result - 1;
+        if count > 0 {
+            self.emit(Op::PopLocals(count as u16));
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Array(token, elements) => {
+                for element in elements {
+                    self.expression(element);
+                }
+                self.at(token);
+                let count = self.operand(elements.len());
+                self.emit(Op::Array(count));
+            }
+            Expr::Assignment(name, expr, _) => {
+                self.expression(expr);
+                self.set_variable(name);
+            }
+            Expr::Binary(op, left, right) => {
+                self.expression(left);
+                self.expression(right);
+                self.at(op);
+                self.emit(Self::binary_op(op));
+            }
+            Expr::Call(token, callee, args) => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+                self.at(token);
+                if args.len() > u8::MAX as usize {
+                    self.error("Too many arguments.");
+                }
+                self.emit(Op::Call(args.len() as u8));
+            }
+            Expr::Dict(token, entries) => {
+                for (key, val) in entries {
+                    let key = self.constant(Value::String(key.clone()));
+                    self.emit(Op::Constant(key));
+                    self.expression(val);
+                }
+                self.at(token);
+                let count = self.operand(entries.len());
+                self.emit(Op::Dict(count));
+            }
+            Expr::Function(parameters, statements) => self.function(None, parameters, statements),
+            Expr::Grouping(expr) => self.expression(expr),
+            Expr::Index(token, array, index) => {
+                self.expression(array);
+                self.expression(index);
+                self.at(token);
+                self.emit(Op::Index);
+            }
+            Expr::IndexAssignment(token, array, index, expr) => {
+                self.expression(array);
+                self.expression(index);
+                self.expression(expr);
+                self.at(token);
+                self.emit(Op::SetIndex);
+            }
+            Expr::Literal(val) => {
+                match val {
+                    Value::Null => self.emit(Op::Null),
+                    Value::Bool(true) => self.emit(Op::True),
+                    Value::Bool(false) => self.emit(Op::False),
+                    _ => {
+                        let i = self.constant(val.clone());
+                        self.emit(Op::Constant(i))
+                    }
+                };
+            }
+            Expr::Logical(op, left, right) => self.logical(op, left, right),
+            Expr::Ternary(_, first, second, third) => {
+                self.expression(first);
+                let jump_else = self.emit(Op::JumpIfFalse(0));
+                self.expression(second);
+                let jump_end = self.emit(Op::Jump(0));
+                self.patch(jump_else);
+                self.expression(third);
+                self.patch(jump_end);
+            }
+            Expr::Unary(op, right) => {
+                self.expression(right);
+                self.at(op);
+                match op.val {
+                    TokenValue::Minus => self.emit(Op::Negate),
+                    TokenValue::Bang => self.emit(Op::Not),
+                    TokenValue::Tilde => self.emit(Op::BitNot),
+                    _ => panic!("Unknown unary operator: {:?}", op),
+                };
+            }
+            Expr::Update(op, target, expr, postfix) => self.update(op, target, expr, *postfix),
+            Expr::Variable(name, _) => self.get_variable(name),
+        }
+    }
+
+    // 対象の変数や配列と添字は一度だけ評価する
+    // 後置なら更新前の値を複製しておき、最後に残す
+    fn update(&mut self, op: &Token, target: &Expr, expr: &Expr, postfix: bool) {
+        match target {
+            Expr::Variable(name, _) => {
+                self.get_variable(name);
+                if postfix {
+                    self.emit(Op::Dup(0));
+                }
+                self.expression(expr);
+                self.at(op);
+                self.emit(Self::binary_op(op));
+                self.set_variable(name);
+                if postfix {
+                    self.emit(Op::Pop);
+                }
+            }
+            Expr::Index(token, array, index) => {
+                self.expression(array);
+                self.expression(index);
+                self.at(token);
+                self.emit(Op::Dup(1));
+                self.emit(Op::Dup(1));
+                self.emit(Op::Index);
+                if postfix {
+                    // 配列、添字、更新前の値、配列、添字、更新前の値
+                    for _ in 0..3 {
+                        self.emit(Op::Dup(2));
+                    }
+                }
+                self.expression(expr);
+                self.at(op);
+                self.emit(Self::binary_op(op));
+                self.at(token);
+                self.emit(Op::SetIndex);
+                if postfix {
+                    self.emit(Op::Pop);
+                    self.emit(Op::PopUnder(2));
+                }
+            }
+            _ => panic!("Invalid update target: {:?}", target),
+        }
+    }
+
+    fn get_variable(&mut self, name: &Token) {
+        self.at(name);
+        let op = match self.lookup(name) {
+            Variable::Local(i) => Op::GetLocal(i),
+            Variable::Upvalue(i) => Op::GetUpvalue(i),
+            Variable::Global(i) => Op::GetGlobal(i),
+        };
+        self.emit(op);
+    }
+
+    fn set_variable(&mut self, name: &Token) {
+        self.at(name);
+        let op = match self.lookup(name) {
+            Variable::Local(i) => Op::SetLocal(i),
+            Variable::Upvalue(i) => Op::SetUpvalue(i),
+            Variable::Global(i) => Op::SetGlobal(i),
+        };
+        self.emit(op);
+    }
+
+    // 論理演算の結果は真偽値にする
+    fn logical(&mut self, op: &Token, left: &Expr, right: &Expr) {
+        self.expression(left);
+        let jump_right = self.emit(Op::JumpIfFalse(0));
+        match op.val {
+            TokenValue::And => {
+                self.expression(right);
+                self.emit(Op::ToBool);
+                let jump_end = self.emit(Op::Jump(0));
+                self.patch(jump_right);
+                self.emit(Op::False);
+                self.patch(jump_end);
+            }
+            TokenValue::Or => {
+                self.emit(Op::True);
+                let jump_end = self.emit(Op::Jump(0));
+                self.patch(jump_right);
+                self.expression(right);
+                self.emit(Op::ToBool);
+                self.patch(jump_end);
+            }
+            _ => panic!("Unknown logical operator: {:?}", op),
+        }
+    }
+
+    // インタプリタと同じく、引数のスコープの内側に本体のスコープを作る
+    fn function(&mut self, name: Option<&Token>, parameters: &[Token], statements: &[Stmt]) {
+        let name = name.map(|name| name.lexeme.clone());
+        self.states
+            .push(State::new(name, parameters.to_vec(), Rc::clone(&self.src)));
+        self.begin_scope();
+        for parameter in parameters {
+            self.add_local(parameter);
+        }
+        self.begin_scope();
+        self.statements(statements);
+        self.emit(Op::Return);
+
+        let state = self.states.pop().expect("No function to compile.");
+        let proto = Rc::new(Self::proto(state));
+        self.state().chunk.functions.push(proto);
+        let i = self.state().chunk.functions.len() - 1;
+        let i = self.operand(i);
+        self.emit(Op::Closure(i));
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    // ブロックの値を残して、ブロックのローカル変数を捨てる
+    fn end_scope(&mut self) {
+        let state = self.state();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        let count = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|l| l.depth > depth)
+            .count();
+        state.locals.truncate(state.locals.len() - count);
+        if count > 0 {
+            let count = self.operand(count);
+            self.emit(Op::PopUnder(count));
+        }
+    }
+
+    // スタックの一番上の値をローカル変数にする
+    fn add_local(&mut self, name: &Token) {
+        if self.state().locals.len() > u16::MAX as usize {
+            self.at(name);
+            self.error("Too many local variables.");
+        }
+        let state = self.state();
+        state.locals.push(Local {
+            name: name.lexeme.clone(),
+            depth: state.scope_depth,
+        });
+    }
+
+    fn binary_op(op: &Token) -> Op {
+        match op.val {
+            TokenValue::Plus => Op::Add,
+            TokenValue::Minus => Op::Subtract,
+            TokenValue::Star => Op::Multiply,
+            TokenValue::Slash => Op::Divide,
+            TokenValue::TildeSlash => Op::FloorDivide,
+            TokenValue::Percent => Op::Modulo,
+            TokenValue::StarStar => Op::Power,
+            TokenValue::Ampersand => Op::BitAnd,
+            TokenValue::Pipe => Op::BitOr,
+            TokenValue::Caret => Op::BitXor,
+            TokenValue::LessLess => Op::ShiftLeft,
+            TokenValue::GreaterGreater => Op::ShiftRight,
+            TokenValue::Less => Op::Less,
+            TokenValue::LessEqual => Op::LessEqual,
+            TokenValue::Greater => Op::Greater,
+            TokenValue::GreaterEqual => Op::GreaterEqual,
+            TokenValue::EqualEqual => Op::Equal,
+            TokenValue::BangEqual => Op::NotEqual,
+            TokenValue::In => Op::In,
+            _ => panic!("Unknown binary operator: {:?}", op),
+        }
+    }
+
+    fn lookup(&mut self, name: &Token) -> Variable {
+        let level = self.states.len() - 1;
+        if let Some(i) = Self::local(&self.states[level], &name.lexeme) {
+            return Variable::Local(i);
+        }
+        if let Some(i) = self.upvalue(level, &name.lexeme) {
+            return Variable::Upvalue(i);
+        }
+        Variable::Global(self.name(name))
+    }
+
+    fn local(state: &State, name: &str) -> Option<u16> {
+        state
+            .locals
+            .iter()
+            .rposition(|l| l.name == name)
+            .map(|i| i as u16)
+    }
+
+    // level番目の関数から見た外側の関数のローカル変数
+    fn upvalue(&mut self, level: usize, name: &str) -> Option<u16> {
+        if level == 0 {
+            return None;
+        }
+        let capture = match Self::local(&self.states[level - 1], name) {
+            Some(i) => Capture::Local(i),
+            None => Capture::Upvalue(self.upvalue(level - 1, name)?),
+        };
+
+        let upvalues = &mut self.states[level].upvalues;
+        if let Some(i) = upvalues.iter().position(|c| *c == capture) {
+            return Some(i as u16);
+        }
+        upvalues.push(capture);
+        let i = upvalues.len() - 1;
+        Some(self.operand(i))
+    }
+
+    fn name(&mut self, name: &Token) -> u16 {
+        if let Some(i) = self.state().names.get(&name.lexeme) {
+            return *i;
+        }
+        let i = self.constant(Value::String(name.lexeme.clone()));
+        self.state().names.insert(name.lexeme.clone(), i);
+        i
+    }
+
+    fn constant(&mut self, val: Value) -> u16 {
+        self.state().chunk.constants.push(val);
+        let i = self.state().chunk.constants.len() - 1;
+        self.operand(i)
+    }
+
+    fn operand(&mut self, n: usize) -> u16 {
+        if n > u16::MAX as usize {
+            self.error("Too many constants or elements.");
+        }
+        n as u16
+    }
+
+    // ジャンプ命令の飛び先を今の位置にする
+    fn patch(&mut self, offset: usize) {
+        let target = self.state().chunk.code.len() as u32;
+        match &mut self.state().chunk.code[offset] {
+            Op::Jump(t) | Op::JumpIfFalse(t) | Op::Try(t) | Op::TryFinally(t) => *t = target,
+            op => panic!("Patching non-jump instruction: {:?}", op),
+        }
+    }
+
+    fn error(&mut self, msg: &str) {
+        self.errors.push(Error::from_token(&self.token, msg));
+    }
+}
+
+enum Variable {
+    Local(u16),
+    Upvalue(u16),
+    Global(u16),
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use crate::ast::Value;
+
+// 挿入順を保つ辞書
+#[derive(Debug, Clone, Default)]
+pub struct Dict {
+    indices: HashMap<String, usize>,
+    entries: Vec<(String, Value)>,
+}
+
+impl Dict {
+    pub fn new() -> Dict {
+        Default::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.indices.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn insert(&mut self, key: String, val: Value) {
+        match self.indices.get(&key) {
+            Some(&i) => self.entries[i].1 = val,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, val));
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // 値の比較方法を指定して比べる（挿入順は問わない）
+    pub fn eq_by(&self, other: &Dict, mut eq: impl FnMut(&Value, &Value) -> bool) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(k, v)| other.get(k).is_some_and(|o| eq(v, o)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+// 同じキーに同じ値があれば等しい
+impl PartialEq for Dict {
+    fn eq(&self, other: &Dict) -> bool {
+        self.eq_by(other, |l, r| l == r)
+    }
+}
+
+impl FromIterator<(String, Value)> for Dict {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Dict {
+        let mut dict = Dict::new();
+        for (key, val) in iter {
+            dict.insert(key, val);
+        }
+        dict
+    }
+}
@@ -1,9 +1,11 @@
+use std::any::Any;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::ast::Value;
 use crate::error::Error;
+use crate::gc::{self, Kind, Trace};
 use crate::token::Token;
 
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -14,43 +16,166 @@ pub struct Environment {
 
 impl Environment {
     pub fn enclosed_by(enclosing: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
-        Rc::new(RefCell::new(Environment {
+        gc::alloc(RefCell::new(Environment {
             vars: HashMap::new(),
             enclosing: Some(Rc::clone(enclosing)),
         }))
     }
 
+    // 宣言した位置の補足はResolverがつける
     pub fn define(&mut self, name: &Token, val: Value) -> Result<Value, Error> {
-        if self.vars.contains_key(&name.lexeme) {
+        if !self.declare(&name.lexeme, val) {
             return Err(Error::from_token(name, "Variable already defined."));
         }
-
-        self.vars.insert(name.lexeme.clone(), val);
         Ok(Value::Null)
     }
 
+    // 定義済みならfalseを返して何もしない
+    pub fn declare(&mut self, name: &str, val: Value) -> bool {
+        if self.vars.contains_key(name) {
+            return false;
+        }
+        self.vars.insert(name.to_string(), val);
+        true
+    }
+
+    // 組み込み関数の登録用（定義済みでも上書きする）
+    pub fn set(&mut self, name: &str, val: Value) {
+        self.vars.insert(name.to_string(), val);
+    }
+
     pub fn assign(&mut self, name: &Token, val: Value) -> Result<Value, Error> {
-        if self.vars.contains_key(&name.lexeme) {
-            self.vars.insert(name.lexeme.clone(), val.clone());
+        if self.replace(&name.lexeme, val.clone()) {
             Ok(val)
-        } else if let Some(enclosing) = &self.enclosing {
-            enclosing.borrow_mut().assign(name, val)
         } else {
             Err(Error::from_token(name, "Variable not defined."))
         }
     }
 
-    pub fn get(&self, name: &Token) -> Result<Value, Error> {
-        match self.vars.get(&name.lexeme) {
+    // 外側の環境もたどって上書きする（定義されていなければfalse）
+    pub fn replace(&mut self, name: &str, val: Value) -> bool {
+        if let Some(var) = self.vars.get_mut(name) {
+            *var = val;
+            true
+        } else if let Some(enclosing) = &self.enclosing {
+            enclosing.borrow_mut().replace(name, val)
+        } else {
+            false
+        }
+    }
+
+    // 外側の環境はたどらない
+    pub fn lookup(&self, name: &str) -> Option<&Value> {
+        self.vars.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.vars.keys()
+    }
+
+    // 同じ変数を持つ別の環境（forの繰り返しごとに変数を分ける）
+    pub fn copy(env: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        gc::alloc(RefCell::new(env.borrow().clone()))
+    }
+
+    // depth個外側の環境
+    pub fn ancestor(env: &Rc<RefCell<Environment>>, depth: usize) -> Rc<RefCell<Environment>> {
+        let mut env = Rc::clone(env);
+        for _ in 0..depth {
+            let enclosing = env
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("Resolved depth exceeds the environment chain.");
+            env = enclosing;
+        }
+        env
+    }
+
+    // スクリプトやモジュールのトップレベルの環境（その外側は組み込み関数だけ）
+    pub fn top_level(env: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let mut env = Rc::clone(env);
+        loop {
+            let enclosing = match &env.borrow().enclosing {
+                Some(enclosing) if enclosing.borrow().enclosing.is_some() => Rc::clone(enclosing),
+                _ => break,
+            };
+            env = enclosing;
+        }
+        env
+    }
+
+    // Resolverが調べた環境だけを見る
+    pub fn get_at(
+        env: &Rc<RefCell<Environment>>,
+        depth: usize,
+        name: &Token,
+    ) -> Result<Value, Error> {
+        let env = Self::ancestor(env, depth);
+        let env = env.borrow();
+        match env.vars.get(&name.lexeme) {
             Some(Value::Undefined) => Err(Error::from_token(name, "Variable not initialized.")),
             Some(val) => Ok(val.clone()),
-            _ => {
-                if let Some(enclosing) = &self.enclosing {
-                    enclosing.borrow().get(name)
-                } else {
-                    Err(Error::from_token(name, "Variable not defined."))
-                }
+            None => Err(Error::from_token(name, "Variable not defined.")),
+        }
+    }
+
+    pub fn assign_at(
+        env: &Rc<RefCell<Environment>>,
+        depth: usize,
+        name: &Token,
+        val: Value,
+    ) -> Result<Value, Error> {
+        let env = Self::ancestor(env, depth);
+        let mut env = env.borrow_mut();
+        match env.vars.get_mut(&name.lexeme) {
+            Some(var) => {
+                *var = val.clone();
+                Ok(val)
             }
+            None => Err(Error::from_token(name, "Variable not defined.")),
+        }
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, Error> {
+        match self.find(&name.lexeme) {
+            Some(Value::Undefined) => Err(Error::from_token(name, "Variable not initialized.")),
+            Some(val) => Ok(val),
+            None => Err(Error::from_token(name, "Variable not defined.")),
+        }
+    }
+
+    // 外側の環境もたどって探す
+    pub fn find(&self, name: &str) -> Option<Value> {
+        match self.vars.get(name) {
+            Some(val) => Some(val.clone()),
+            None => self.enclosing.as_ref()?.borrow().find(name),
+        }
+    }
+}
+
+impl Trace for RefCell<Environment> {
+    fn kind(&self) -> Kind {
+        Kind::Environment
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(env) = self.try_borrow() else {
+            return false;
+        };
+        for val in env.vars.values() {
+            gc::trace_value(val, visit);
+        }
+        if let Some(enclosing) = &env.enclosing {
+            gc::trace_rc(enclosing, visit);
+        }
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        match self.try_borrow_mut() {
+            Ok(mut env) => Box::new((std::mem::take(&mut env.vars), env.enclosing.take())),
+            Err(_) => Box::new(()),
         }
     }
 }
@@ -1,20 +1,158 @@
-use crate::token::{Token, TokenValue};
+use crate::token::{Span, Token, TokenValue};
+use std::io::IsTerminal;
 
-// TODO: 文字の位置を覚える
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Error {
     pub line: usize,
-    pub location: String,
+    pub location: Box<str>,
     pub msg: String,
+    pub kind: ErrorKind,
+    // モジュールの中で起きたエラーの場合はそのファイル
+    pub file: Option<Box<str>>,
+    // ソース上の位置がわからない場合はNone
+    // Resultが大きくなりすぎないようにBoxに入れる
+    pub span: Option<Box<Span>>,
+    // 詳細表示で添える補足
+    pub notes: Vec<Note>,
+    // エラーが起きるまでの関数呼び出し（内側から順に）
+    // spanと同じくResultを大きくしないためにBoxに入れる
+    pub trace: Option<Box<Vec<Call>>>,
+    // 一緒に報告するエラー（モジュールの2つ目以降の構文エラーなど）
+    // traceと同じくResultを大きくしないためにBoxに入れる
+    pub others: Option<Box<Vec<Error>>>,
+}
+
+// 実行の打ち切りはスクリプトのエラーと違ってcatchで捕まえられない
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ErrorKind {
+    #[default]
+    Script,
+    // 閉じていない文字列やコメント（REPLでは続きの行を読む）
+    Unterminated,
+    // 実行できるステップ数を使い切った
+    OutOfFuel,
+    Timeout,
+    // ホストアプリケーションが割り込んだ
+    Interrupted,
+}
+
+impl ErrorKind {
+    pub fn is_abort(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::OutOfFuel | ErrorKind::Timeout | ErrorKind::Interrupted
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Note {
+    pub line: usize,
+    pub msg: String,
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Call {
+    // letで定義した関数の名前
+    pub function: Option<String>,
+    // 呼び出した位置（ホストアプリケーションからの呼び出しは0）
+    pub line: usize,
+}
+
+impl std::fmt::Display for Call {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.function {
+            Some(name) => write!(f, "in {}", name)?,
+            None => write!(f, "in anonymous function")?,
+        }
+        if self.line > 0 {
+            write!(f, ", called at line {}", self.line)?;
+        }
+        Ok(())
+    }
 }
 
 impl Error {
     pub fn new(line: usize, location: &str, msg: &str) -> Error {
         Error {
             line,
-            location: location.to_string(),
+            location: location.into(),
             msg: msg.to_string(),
+            kind: ErrorKind::Script,
+            file: None,
+            span: None,
+            notes: vec![],
+            trace: None,
+            others: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Error {
+        self.span = Some(Box::new(span));
+        self
+    }
+
+    pub fn with_kind(mut self, kind: ErrorKind) -> Error {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_note(mut self, line: usize, msg: &str, span: Option<Span>) -> Error {
+        self.notes.push(Note {
+            line,
+            msg: msg.to_string(),
+            span,
+        });
+        self
+    }
+
+    // エラーが関数の外に伝わるときに呼び出しを記録する
+    pub fn called_from(mut self, call: Call) -> Error {
+        self.trace.get_or_insert_with(Default::default).push(call);
+        self
+    }
+
+    pub fn trace(&self) -> &[Call] {
+        self.trace.as_deref().map_or(&[], Vec::as_slice)
+    }
+
+    // 再帰で同じ呼び出しが続くところはまとめて表示する
+    fn trace_lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        for calls in self.trace().chunk_by(|a, b| a == b) {
+            lines.push(calls[0].to_string());
+            if calls.len() > 1 {
+                lines.push(format!("(repeated {} more times)", calls.len() - 1));
+            }
+        }
+        lines
+    }
+
+    // 最初のエラーに残りのエラーを添えて1つにまとめる
+    pub fn combine(errors: Vec<Error>) -> Option<Error> {
+        let mut errors = errors.into_iter();
+        let mut first = errors.next()?;
+        let others: Vec<_> = errors.collect();
+        if !others.is_empty() {
+            first.others = Some(Box::new(others));
         }
+        Some(first)
+    }
+
+    // まとめたエラーを元の並びに戻す
+    pub fn into_all(mut self) -> Vec<Error> {
+        let others = self.others.take().map_or(vec![], |others| *others);
+        let mut errors = vec![self];
+        errors.extend(others.into_iter().flat_map(Error::into_all));
+        errors
+    }
+
+    pub fn in_file(mut self, file: &str) -> Error {
+        if self.file.is_none() {
+            self.file = Some(file.into());
+        }
+        self
     }
 
     pub fn from_token(token: &Token, msg: &str) -> Error {
@@ -22,18 +160,190 @@ impl Error {
             TokenValue::EOF => Self::new(token.line, "end", msg),
             _ => Self::new(token.line, &token.lexeme, msg),
         }
+        .with_span(token.span)
     }
 
     pub fn report(&self) {
+        eprintln!("{}", self)
+    }
+
+    // ソースの該当行と下線を付けて表示する
+    pub fn report_with_source(&self, src: &str) {
+        eprint!("{}", self.render(src, std::io::stderr().is_terminal()))
+    }
+
+    // srcはエラーが起きたファイルのソース
+    pub fn render(&self, src: &str, color: bool) -> String {
+        let style = Style::new(color);
+        let mut out = format!(
+            "{}: {}\n",
+            style.paint(Style::ERROR, "error"),
+            style.paint(Style::BOLD, &self.msg)
+        );
+
+        let column = match &self.span {
+            Some(span) if span.column > 0 => format!(":{}", span.column),
+            _ => "".to_string(),
+        };
+        let file = match &self.file {
+            Some(file) => format!("{}:", file),
+            None => "line ".to_string(),
+        };
+        let width = self
+            .notes
+            .iter()
+            .map(|note| note.line)
+            .chain([self.line])
+            .max()
+            .unwrap_or(0)
+            .to_string()
+            .len();
+        let gutter = " ".repeat(width);
+        out.push_str(&format!(
+            "{}{} {}{}{}\n",
+            gutter,
+            style.paint(Style::GUTTER, "-->"),
+            file,
+            self.line,
+            column
+        ));
+
+        if let Some(span) = &self.span {
+            out.push_str(&snippet(src, self.line, span, '^', "", width, &style));
+        }
+        for note in &self.notes {
+            match &note.span {
+                Some(span) => out.push_str(&snippet(
+                    src, note.line, span, '-', &note.msg, width, &style,
+                )),
+                None => out.push_str(&format!(
+                    "{} {} note: {}\n",
+                    gutter,
+                    style.paint(Style::GUTTER, "="),
+                    note.msg
+                )),
+            }
+        }
+        for line in self.trace_lines() {
+            out.push_str(&format!(
+                "{} {} {}\n",
+                gutter,
+                style.paint(Style::GUTTER, "="),
+                line
+            ));
+        }
+        out
+    }
+}
+
+// 行番号つきでソースの1行を示し、範囲に下線を引く
+fn snippet(
+    src: &str,
+    line: usize,
+    span: &Span,
+    mark: char,
+    label: &str,
+    width: usize,
+    style: &Style,
+) -> String {
+    if span.column == 0 || span.start > src.len() || !src.is_char_boundary(span.start) {
+        return "".to_string();
+    }
+    let begin = src[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let text = src[begin..].lines().next().unwrap_or("");
+
+    // タブはそのまま残して下線の位置を合わせる
+    let indent: String = text
+        .chars()
+        .take(span.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let length = if span.end_line == line && span.end_column > span.column {
+        span.end_column - span.column
+    } else {
+        text.chars().count().saturating_sub(span.column - 1)
+    };
+    let underline = mark.to_string().repeat(length.max(1));
+    let color = if mark == '^' {
+        Style::ERROR
+    } else {
+        Style::NOTE
+    };
+    let label = if label.is_empty() {
+        "".to_string()
+    } else {
+        format!(" {}", label)
+    };
+
+    let gutter = style.paint(Style::GUTTER, "|");
+    format!(
+        "{:w$} {}\n{:>w$} {} {}\n{:w$} {} {}{}\n",
+        "",
+        gutter,
+        line,
+        gutter,
+        text,
+        "",
+        gutter,
+        indent,
+        style.paint(color, &format!("{}{}", underline, label)),
+        w = width
+    )
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    const ERROR: &'static str = "1;31";
+    const NOTE: &'static str = "1;34";
+    const GUTTER: &'static str = "34";
+    const BOLD: &'static str = "1";
+
+    fn new(color: bool) -> Style {
+        Style { color }
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let file = match &self.file {
+            Some(file) => format!("{} ", file),
+            None => "".to_string(),
+        };
+        let column = match &self.span {
+            Some(span) => format!(":{}", span.column),
+            None => "".to_string(),
+        };
         if self.location.is_empty() {
-            eprintln!("[line {}] Error: {}", self.line, self.msg)
+            write!(
+                f,
+                "[{}line {}{}] Error: {}",
+                file, self.line, column, self.msg
+            )?;
         } else {
-            eprintln!(
-                "[line {}] Error at '{}': {}",
+            write!(
+                f,
+                "[{}line {}{}] Error at '{}': {}",
+                file,
                 self.line,
+                column,
                 self.location.escape_debug(),
                 self.msg
-            )
+            )?;
+        }
+        for line in self.trace_lines() {
+            write!(f, "\n  {}", line)?;
         }
+        Ok(())
     }
 }
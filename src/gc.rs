@@ -0,0 +1,308 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::ast::{Function, Module, Value};
+use crate::chunk::{Closure, Upvalue};
+use crate::dict::Dict;
+
+// 循環参照を回収するための管理
+//
+// 環境や関数などは参照カウントで管理しているが、再帰関数と環境のように
+// 互いに参照し合うと解放されない。管理対象のオブジェクトを弱参照で登録しておき、
+// 管理対象どうしの参照だけでは説明できない参照カウントを持つものを根として、
+// そこから辿れないものの中身を空にして循環を断つ。
+// スタックやホストアプリケーションからの参照は参照カウントに表れるので、
+// 根を列挙しなくても実行中に回収できる。
+
+// この数だけ確保するまでは回収しない
+const MIN_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Kind {
+    Environment,
+    Array,
+    Dict,
+    Function,
+    Upvalue,
+    Module,
+}
+
+// 生きているオブジェクトの数（関数はVMのクロージャも含む）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Counts {
+    pub environments: usize,
+    pub arrays: usize,
+    pub dicts: usize,
+    pub functions: usize,
+    pub upvalues: usize,
+    pub modules: usize,
+}
+
+impl Counts {
+    pub fn total(&self) -> usize {
+        self.environments + self.arrays + self.dicts + self.functions + self.upvalues + self.modules
+    }
+}
+
+pub(crate) trait Trace {
+    fn kind(&self) -> Kind;
+
+    // 直接参照している管理対象のオブジェクトを渡す（借用中で調べられなければfalse）
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool;
+
+    // 循環を断つために中身を取り出す（取り出したものは回収の最後に捨てる）
+    fn clear(&self) -> Box<dyn Any>;
+}
+
+#[derive(Default)]
+struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    // 前回の回収から確保した数
+    allocated: usize,
+    threshold: usize,
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap {
+        threshold: MIN_THRESHOLD,
+        ..Heap::default()
+    });
+}
+
+// 管理対象のオブジェクトを確保する
+pub(crate) fn alloc<T: Trace + 'static>(object: T) -> Rc<T> {
+    if HEAP.with(|heap| heap.borrow().allocated >= heap.borrow().threshold) {
+        collect();
+    }
+    let object = Rc::new(object);
+    let weak: Weak<dyn Trace> = Rc::downgrade(&object) as Weak<dyn Trace>;
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.push(weak);
+        heap.allocated += 1;
+    });
+    object
+}
+
+// 循環参照だけで生きているオブジェクトを回収し、その数を返す
+pub fn collect() -> usize {
+    let objects: Vec<Rc<dyn Trace>> = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.retain(|weak| weak.strong_count() > 0);
+        heap.objects.iter().filter_map(Weak::upgrade).collect()
+    });
+
+    let index: HashMap<*const (), usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| (Rc::as_ptr(object) as *const (), i))
+        .collect();
+    let mut edges = vec![vec![]; objects.len()];
+    let mut internal = vec![0; objects.len()];
+    let mut marked = vec![false; objects.len()];
+    for (i, object) in objects.iter().enumerate() {
+        let traced = object.trace(&mut |ptr| {
+            if let Some(&j) = index.get(&ptr) {
+                edges[i].push(j);
+            }
+        });
+        // 借用中のものは使われているので根とする
+        if !traced {
+            marked[i] = true;
+        }
+    }
+    for &j in edges.iter().flatten() {
+        internal[j] += 1;
+    }
+
+    // ここで取り出した分を除いて、管理対象の外から参照されているものが根
+    let mut stack: Vec<usize> = (0..objects.len())
+        .filter(|&i| marked[i] || Rc::strong_count(&objects[i]) - 1 > internal[i])
+        .collect();
+    for &i in &stack {
+        marked[i] = true;
+    }
+    while let Some(i) = stack.pop() {
+        for &j in &edges[i] {
+            if !marked[j] {
+                marked[j] = true;
+                stack.push(j);
+            }
+        }
+    }
+
+    let garbage: Vec<Box<dyn Any>> = objects
+        .iter()
+        .zip(&marked)
+        .filter(|(_, &marked)| !marked)
+        .map(|(object, _)| object.clear())
+        .collect();
+    let freed = garbage.len();
+    drop(garbage);
+    drop(objects);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.objects.retain(|weak| weak.strong_count() > 0);
+        heap.allocated = 0;
+        heap.threshold = MIN_THRESHOLD.max(heap.objects.len() * 2);
+    });
+    freed
+}
+
+// このスレッドで生きている管理対象のオブジェクトの数
+pub fn live() -> Counts {
+    let mut counts = Counts::default();
+    HEAP.with(|heap| {
+        for object in heap.borrow().objects.iter().filter_map(Weak::upgrade) {
+            match object.kind() {
+                Kind::Environment => counts.environments += 1,
+                Kind::Array => counts.arrays += 1,
+                Kind::Dict => counts.dicts += 1,
+                Kind::Function => counts.functions += 1,
+                Kind::Upvalue => counts.upvalues += 1,
+                Kind::Module => counts.modules += 1,
+            }
+        }
+    });
+    counts
+}
+
+// 値が直接参照している管理対象のオブジェクト
+pub(crate) fn trace_value(val: &Value, visit: &mut dyn FnMut(*const ())) {
+    match val {
+        Value::Array(array) => visit(Rc::as_ptr(array) as *const ()),
+        Value::Dict(dict) => visit(Rc::as_ptr(dict) as *const ()),
+        Value::Function(func) => visit(Rc::as_ptr(func) as *const ()),
+        Value::Closure(closure) => visit(Rc::as_ptr(closure) as *const ()),
+        Value::Module(module) => visit(Rc::as_ptr(module) as *const ()),
+        _ => {}
+    }
+}
+
+pub(crate) fn trace_rc<T>(rc: &Rc<T>, visit: &mut dyn FnMut(*const ())) {
+    visit(Rc::as_ptr(rc) as *const ())
+}
+
+impl Trace for RefCell<Vec<Value>> {
+    fn kind(&self) -> Kind {
+        Kind::Array
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(elements) = self.try_borrow() else {
+            return false;
+        };
+        for element in elements.iter() {
+            trace_value(element, visit);
+        }
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        match self.try_borrow_mut() {
+            Ok(mut elements) => Box::new(std::mem::take(&mut *elements)),
+            Err(_) => Box::new(()),
+        }
+    }
+}
+
+impl Trace for RefCell<Dict> {
+    fn kind(&self) -> Kind {
+        Kind::Dict
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(dict) = self.try_borrow() else {
+            return false;
+        };
+        for (_, val) in dict.iter() {
+            trace_value(val, visit);
+        }
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        match self.try_borrow_mut() {
+            Ok(mut dict) => Box::new(std::mem::take(&mut *dict)),
+            Err(_) => Box::new(()),
+        }
+    }
+}
+
+// 関数やモジュールは書き換えられないので、循環は環境の側で断つ
+impl Trace for Function {
+    fn kind(&self) -> Kind {
+        Kind::Function
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        trace_rc(&self.env, visit);
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        Box::new(())
+    }
+}
+
+impl Trace for Module {
+    fn kind(&self) -> Kind {
+        Kind::Module
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        trace_rc(&self.env, visit);
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        Box::new(())
+    }
+}
+
+impl Trace for Closure {
+    fn kind(&self) -> Kind {
+        Kind::Function
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        for upvalue in &self.upvalues {
+            trace_rc(upvalue, visit);
+        }
+        trace_rc(&self.globals, visit);
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        Box::new(())
+    }
+}
+
+impl Trace for RefCell<Upvalue> {
+    fn kind(&self) -> Kind {
+        Kind::Upvalue
+    }
+
+    fn trace(&self, visit: &mut dyn FnMut(*const ())) -> bool {
+        let Ok(upvalue) = self.try_borrow() else {
+            return false;
+        };
+        if let Upvalue::Closed(val) = &*upvalue {
+            trace_value(val, visit);
+        }
+        true
+    }
+
+    fn clear(&self) -> Box<dyn Any> {
+        match self.try_borrow_mut() {
+            Ok(mut upvalue) => Box::new(std::mem::replace(
+                &mut *upvalue,
+                Upvalue::Closed(Value::Null),
+            )),
+            Err(_) => Box::new(()),
+        }
+    }
+}
@@ -1,37 +1,110 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
 use std::rc::Rc;
 
-use crate::ast::{Expr, Stmt, Value};
+use crate::ast::{Depth, Expr, Function, Module, Native, Stmt, Value};
+use crate::budget::Budget;
+use crate::builtins;
 use crate::env::Environment;
-use crate::error::Error;
+use crate::error::{Call, Error};
+use crate::gc;
+use crate::loader::{Load, Loader};
+use crate::ops;
+use crate::resolver::Resolver;
+use crate::session::{DEFAULT_MAX_DEPTH, DEFAULT_STACK_SIZE};
 use crate::token::{Token, TokenValue};
 
+// Resultが大きくなりすぎないようにトークンやエラーはBoxに入れる
 #[derive(Debug, PartialEq)]
 pub enum Runtime {
-    Break(Token, Value),
+    Break(Box<Token>, Value),
+    Continue(Box<Token>),
     Error(Error),
     Return(Value),
+    // 捕まえられなかったときのエラーと、投げられた値
+    Throw(Box<Error>, Value),
 }
 
 impl Runtime {
     fn from_token(token: &Token, msg: &str) -> Runtime {
         Runtime::Error(Error::from_token(token, msg))
     }
+
+    // catchで受け取る値（組み込みのエラーも値にする）
+    fn exception(self) -> Result<Value> {
+        match self {
+            Runtime::Error(error) if !error.kind.is_abort() => Ok(Value::Error(Rc::new(error))),
+            Runtime::Throw(_, val) => Ok(val),
+            runtime => Err(runtime),
+        }
+    }
+
+    // 関数から抜けるエラーに呼び出しを記録する
+    fn called_from(self, call: Call) -> Runtime {
+        match self {
+            Runtime::Error(error) => Runtime::Error(error.called_from(call)),
+            Runtime::Throw(error, val) => Runtime::Throw(Box::new(error.called_from(call)), val),
+            runtime => runtime,
+        }
+    }
 }
 
 type Result<T, R = Runtime> = std::result::Result<T, R>;
 
 pub struct Interpreter {
+    // 組み込み関数の入った、すべてのスクリプトとモジュールの外側の環境
+    globals: Rc<RefCell<Environment>>,
     env: Rc<RefCell<Environment>>,
+    loader: Loader,
+    // 実行中のモジュールでexportされた変数
+    exports: Vec<String>,
+    // 実行中の関数呼び出し
+    calls: Vec<Call>,
+    max_depth: usize,
+    // 関数呼び出しで使ってよいネイティブのスタックの大きさ
+    stack_limit: usize,
+    // 一番外側の関数呼び出しのときのネイティブのスタックの位置
+    stack_base: usize,
+    budget: Budget,
+}
+
+// 今のネイティブのスタックの位置
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
+        let globals = Rc::new(RefCell::new(Environment::default()));
+        builtins::define(&mut globals.borrow_mut());
         Interpreter {
-            env: Rc::new(RefCell::new(Default::default())),
+            env: Environment::enclosed_by(&globals),
+            globals,
+            loader: Loader::default(),
+            exports: vec![],
+            calls: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack_limit: DEFAULT_STACK_SIZE / 2,
+            stack_base: 0,
+            budget: Budget::default(),
         }
     }
 
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> std::result::Result<Value, String> + 'static,
+    ) {
+        self.globals
+            .borrow_mut()
+            .set(name, Value::native(name, arity, func));
+    }
+
     pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Value, Vec<Error>> {
         let mut result = Ok(Value::Null);
 
@@ -42,27 +115,109 @@ impl Interpreter {
             }
         }
 
+        Self::top_level(result)
+    }
+
+    // これまでに定義された変数を踏まえて、変数の参照先を調べる
+    pub fn resolve(&self, statements: Vec<Stmt>) -> Result<Vec<Stmt>, Vec<Error>> {
+        let defined = Environment::top_level(&self.env)
+            .borrow()
+            .names()
+            .cloned()
+            .collect();
+        Resolver::new(defined, self.builtin_names()).resolve(statements)
+    }
+
+    fn builtin_names(&self) -> HashSet<String> {
+        self.globals.borrow().names().cloned().collect()
+    }
+
+    // ホストアプリケーションから関数を呼ぶ
+    pub fn call_from_host(
+        &mut self,
+        name: &str,
+        func: &Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Vec<Error>> {
+        let token = Token::new(TokenValue::Identifier, name.to_string(), 0);
+        let result = self.call_value(&token, func, args);
+        // ソース上に呼び出し位置はない
+        Self::top_level(result).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|e| match e.line {
+                    0 => Error { span: None, ..e },
+                    _ => e,
+                })
+                .collect()
+        })
+    }
+
+    pub fn set_base_dir(&mut self, dir: PathBuf) {
+        self.loader.set_base_dir(dir);
+    }
+
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    // 残りの半分は、最後の呼び出しの中で式を評価したりエラーを返したりするのに使う
+    pub fn set_stack_size(&mut self, size: usize) {
+        self.stack_limit = size / 2;
+    }
+
+    pub(crate) fn budget(&mut self) -> &mut Budget {
+        &mut self.budget
+    }
+
+    // スクリプトで定義された変数、組み込み関数の順に探す
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let val = self.env.borrow().lookup(name).cloned();
+        val.or_else(|| self.globals.borrow().lookup(name).cloned())
+    }
+
+    pub fn set_global(&mut self, name: &str, val: Value) {
+        self.env.borrow_mut().set(name, val)
+    }
+
+    fn top_level(result: Result<Value>) -> Result<Value, Vec<Error>> {
         match result {
             Ok(v) => Ok(v),
             Err(Runtime::Return(v)) => Ok(v),
-            Err(Runtime::Error(e)) => Err(vec![e]),
+            Err(Runtime::Error(e)) => Err(e.into_all()),
             Err(Runtime::Break(ref token, _)) => {
                 Err(vec![Error::from_token(token, "Break from top level")])
             }
+            Err(Runtime::Continue(ref token)) => {
+                Err(vec![Error::from_token(token, "Continue from top level")])
+            }
+            Err(Runtime::Throw(error, _)) => Err(vec![*error]),
         }
     }
 
     fn execute(&mut self, stmt: &Stmt) -> Result<Value> {
+        self.budget.step().map_err(Runtime::Error)?;
         match stmt {
             Stmt::Block(statements) => self.block(statements),
             Stmt::Break(token, expr) => self.break_(token, expr),
+            Stmt::Continue(token) => Err(Runtime::Continue(Box::new(token.clone()))),
+            Stmt::Export(statement) => self.export(statement),
             Stmt::Expression(expr) => self.evaluate(expr),
+            Stmt::For(init, condition, increment, statement) => {
+                self.for_(init, condition, increment, statement)
+            }
+            Stmt::ForIn(name, token, collection, statement) => {
+                self.for_in(name, token, collection, statement)
+            }
             Stmt::If(condition, consequence, alternative) => {
                 self.if_(condition, consequence, alternative)
             }
-            Stmt::Let(name, expr) => self.let_(name, expr),
+            Stmt::Import(token, path, name) => self.import(token, path, name),
+            Stmt::Let(name, expr, _) => self.let_(name, expr),
             Stmt::Print(expr) => self.print(expr),
-            Stmt::Return(expr) => self.return_(expr),
+            Stmt::Return(_, expr) => self.return_(expr),
+            Stmt::Throw(token, expr) => self.throw(token, expr),
+            Stmt::Try(_, body, catch, finally) => self.try_(body, catch, finally),
             Stmt::While(condition, statement) => self.while_(condition, statement),
         }
     }
@@ -89,7 +244,56 @@ impl Interpreter {
         if let Some(expr) = expr {
             val = self.evaluate(expr)?;
         }
-        Err(Runtime::Break(token.clone(), val))
+        Err(Runtime::Break(Box::new(token.clone()), val))
+    }
+
+    fn export(&mut self, statement: &Stmt) -> Result<Value> {
+        let Stmt::Let(name, _, _) = statement else {
+            panic!("Exporting non-let statement: {:?}", statement);
+        };
+        self.execute(statement)?;
+        self.exports.push(name.lexeme.clone());
+        Ok(Value::Null)
+    }
+
+    fn import(&mut self, token: &Token, path: &str, name: &Token) -> Result<Value> {
+        let module = self.load_module(token, path)?;
+        self.env
+            .borrow_mut()
+            .define(name, module)
+            .map_err(Runtime::Error)
+    }
+
+    fn load_module(&mut self, token: &Token, path: &str) -> Result<Value> {
+        let source = match self.loader.load(
+            |msg| Error::from_token(token, msg),
+            path,
+            self.builtin_names(),
+        ) {
+            Ok(Load::Cached(module)) => return Ok(module),
+            Ok(Load::Source(source)) => source,
+            Err(e) => return Err(Runtime::Error(e)),
+        };
+
+        let env = Environment::enclosed_by(&self.globals);
+        let previous_env = std::mem::replace(&mut self.env, Rc::clone(&env));
+        let previous_exports = std::mem::take(&mut self.exports);
+        self.loader.enter(&source);
+
+        let result = self.interpret(&source.statements);
+
+        self.loader.leave();
+        let exports = std::mem::replace(&mut self.exports, previous_exports);
+        self.env = previous_env;
+        result.map_err(|errors| Runtime::Error(Loader::error(errors, &source.file)))?;
+
+        let module = Value::Module(gc::alloc(Module {
+            path: source.file.clone(),
+            env,
+            exports,
+        }));
+        self.loader.cache(source, module.clone());
+        Ok(module)
     }
 
     fn if_(
@@ -99,7 +303,7 @@ impl Interpreter {
         alternative: &Option<Box<Stmt>>,
     ) -> Result<Value> {
         let cond = self.evaluate(condition)?;
-        if Self::is_truthy(&cond) {
+        if ops::is_truthy(&cond) {
             Ok(self.execute(consequence)?)
         } else if let Some(alt) = alternative {
             Ok(self.execute(alt)?)
@@ -109,7 +313,12 @@ impl Interpreter {
     }
 
     fn let_(&mut self, name: &Token, expr: &Expr) -> Result<Value> {
-        let val = self.evaluate(expr)?;
+        let val = match expr {
+            Expr::Function(parameters, statements) => {
+                self.function(Some(name), parameters, statements)?
+            }
+            _ => self.evaluate(expr)?,
+        };
         self.env
             .borrow_mut()
             .define(name, val)
@@ -130,41 +339,242 @@ impl Interpreter {
         Err(Runtime::Return(val))
     }
 
+    fn throw(&mut self, token: &Token, expr: &Expr) -> Result<Value> {
+        match self.evaluate(expr)? {
+            // 捕まえた組み込みのエラーは元のエラーとして投げ直す
+            Value::Error(error) => Err(Runtime::Error((*error).clone())),
+            val => Err(Runtime::Throw(
+                Box::new(Error::from_token(token, &ops::uncaught(&val))),
+                val,
+            )),
+        }
+    }
+
+    // finally節はbreakやreturnで抜けるときも実行する
+    fn try_(
+        &mut self,
+        body: &Stmt,
+        catch: &Option<(Token, Box<Stmt>)>,
+        finally: &Option<Box<Stmt>>,
+    ) -> Result<Value> {
+        let mut result = self.execute(body);
+        if let Some((name, handler)) = catch {
+            result = result.or_else(|runtime| {
+                let exception = runtime.exception()?;
+                self.catch(name, exception, handler)
+            });
+        }
+        // 打ち切られたときはfinally節も実行しない
+        if matches!(&result, Err(Runtime::Error(error)) if error.kind.is_abort()) {
+            return result;
+        }
+        if let Some(finally) = finally {
+            self.execute(finally)?;
+        }
+        result
+    }
+
+    // 例外を入れる変数の環境の内側でcatch節の本体を実行する
+    fn catch(&mut self, name: &Token, exception: Value, handler: &Stmt) -> Result<Value> {
+        let env = Environment::enclosed_by(&self.env);
+        env.borrow_mut()
+            .define(name, exception)
+            .map_err(Runtime::Error)?;
+        let previous = std::mem::replace(&mut self.env, env);
+        let result = self.execute(handler);
+        self.env = previous;
+        result
+    }
+
     fn while_(&mut self, condition: &Expr, statement: &Stmt) -> Result<Value> {
-        let mut result = Ok(Value::Null);
-        while Self::is_truthy(&self.evaluate(condition)?) {
-            result = self.execute(statement);
-            if let Err(Runtime::Break(_, val)) = result {
-                result = Ok(val);
-                break;
-            } else if result.is_err() {
-                break;
+        let mut result = Value::Null;
+        while ops::is_truthy(&self.evaluate(condition)?) {
+            match self.loop_body(statement) {
+                ControlFlow::Continue(val) => result = val,
+                ControlFlow::Break(result) => return result,
             }
         }
+        Ok(result)
+    }
+
+    fn for_(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        condition: &Option<Box<Expr>>,
+        increment: &Option<Box<Expr>>,
+        statement: &Stmt,
+    ) -> Result<Value> {
+        let previous = Rc::clone(&self.env);
+        self.env = Environment::enclosed_by(&previous);
+        let result = self.for_loop(init, condition, increment, statement);
+        self.env = previous;
         result
     }
 
+    fn for_loop(
+        &mut self,
+        init: &Option<Box<Stmt>>,
+        condition: &Option<Box<Expr>>,
+        increment: &Option<Box<Expr>>,
+        statement: &Stmt,
+    ) -> Result<Value> {
+        if let Some(init) = init {
+            self.execute(init)?;
+        }
+        let mut result = Value::Null;
+        loop {
+            if let Some(condition) = condition {
+                if !ops::is_truthy(&self.evaluate(condition)?) {
+                    return Ok(result);
+                }
+            }
+            match self.loop_body(statement) {
+                ControlFlow::Continue(val) => result = val,
+                ControlFlow::Break(result) => return result,
+            }
+            // 本体で作ったクロージャが捕捉した変数は、更新の前に切り離す
+            self.env = Environment::copy(&self.env);
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+    }
+
+    fn for_in(
+        &mut self,
+        name: &Token,
+        token: &Token,
+        collection: &Expr,
+        statement: &Stmt,
+    ) -> Result<Value> {
+        let collection = self.evaluate(collection)?;
+        let elements = ops::iterate(collection).map_err(|msg| Runtime::from_token(token, msg))?;
+
+        let mut result = Value::Null;
+        for element in elements {
+            // 繰り返しごとに変数の環境を作る
+            let env = Environment::enclosed_by(&self.env);
+            env.borrow_mut()
+                .define(name, element)
+                .map_err(Runtime::Error)?;
+            let previous = std::mem::replace(&mut self.env, env);
+            let flow = self.loop_body(statement);
+            self.env = previous;
+            match flow {
+                ControlFlow::Continue(val) => result = val,
+                ControlFlow::Break(result) => return result,
+            }
+        }
+        Ok(result)
+    }
+
+    // ループの本体を実行し、breakやエラーで抜けるときはBreakにする
+    fn loop_body(&mut self, statement: &Stmt) -> ControlFlow<Result<Value>, Value> {
+        match self.execute(statement) {
+            Ok(val) => ControlFlow::Continue(val),
+            Err(Runtime::Break(_, val)) => ControlFlow::Break(Ok(val)),
+            Err(Runtime::Continue(_)) => ControlFlow::Continue(Value::Null),
+            Err(runtime) => ControlFlow::Break(Err(runtime)),
+        }
+    }
+
     fn evaluate(&mut self, expr: &Expr) -> Result<Value> {
+        self.budget.step().map_err(Runtime::Error)?;
         match expr {
-            Expr::Assignment(name, expr) => self.assignment(name, expr),
+            Expr::Array(_token, elements) => self.array(elements),
+            Expr::Assignment(name, expr, depth) => self.assignment(name, expr, depth),
             Expr::Binary(op, left, right) => self.binary(op, left, right),
             Expr::Call(token, callee, args) => self.call(token, callee, args),
-            Expr::Function(parameters, statements) => self.function(parameters, statements),
+            Expr::Dict(_token, entries) => self.dict(entries),
+            Expr::Function(parameters, statements) => self.function(None, parameters, statements),
             Expr::Grouping(expr) => self.evaluate(expr),
+            Expr::Index(token, array, index) => self.index(token, array, index),
+            Expr::IndexAssignment(token, array, index, expr) => {
+                self.index_assignment(token, array, index, expr)
+            }
             Expr::Literal(value) => Ok(value.clone()),
             Expr::Logical(op, left, right) => self.logical(op, left, right),
             Expr::Ternary(op, first, second, third) => self.ternary(op, first, second, third),
             Expr::Unary(op, right) => self.unary(op, right),
-            Expr::Variable(name) => self.variable(name),
+            Expr::Update(op, target, expr, postfix) => self.update(op, target, expr, *postfix),
+            Expr::Variable(name, depth) => self.variable(name, depth),
+        }
+    }
+
+    fn array(&mut self, elements: &[Expr]) -> Result<Value> {
+        let mut vals = vec![];
+        for element in elements {
+            vals.push(self.evaluate(element)?);
+        }
+        Ok(ops::array(vals))
+    }
+
+    fn dict(&mut self, entries: &[(String, Expr)]) -> Result<Value> {
+        let mut vals = vec![];
+        for (key, val) in entries {
+            vals.push((key.clone(), self.evaluate(val)?));
         }
+        Ok(ops::dict(vals))
     }
 
-    fn assignment(&mut self, name: &Token, expr: &Expr) -> Result<Value> {
+    fn index(&mut self, token: &Token, array: &Expr, index: &Expr) -> Result<Value> {
+        let array_val = self.evaluate(array)?;
+        let index_val = self.evaluate(index)?;
+        ops::index(array_val, index_val).map_err(|msg| Runtime::from_token(token, msg))
+    }
+
+    fn index_assignment(
+        &mut self,
+        token: &Token,
+        array: &Expr,
+        index: &Expr,
+        expr: &Expr,
+    ) -> Result<Value> {
+        let array_val = self.evaluate(array)?;
+        let index_val = self.evaluate(index)?;
         let val = self.evaluate(expr)?;
-        self.env
-            .borrow_mut()
-            .assign(name, val)
-            .map_err(Runtime::Error)
+        ops::set_index(array_val, index_val, val).map_err(|msg| Runtime::from_token(token, msg))
+    }
+
+    fn assignment(&mut self, name: &Token, expr: &Expr, depth: &Depth) -> Result<Value> {
+        let val = self.evaluate(expr)?;
+        self.assign(name, depth, val)
+    }
+
+    fn assign(&mut self, name: &Token, depth: &Depth, val: Value) -> Result<Value> {
+        match depth {
+            Some(depth) => Environment::assign_at(&self.env, *depth, name, val),
+            None => Environment::top_level(&self.env)
+                .borrow_mut()
+                .assign(name, val),
+        }
+        .map_err(Runtime::Error)
+    }
+
+    // 対象の変数や配列と添字は一度だけ評価する
+    fn update(&mut self, op: &Token, target: &Expr, expr: &Expr, postfix: bool) -> Result<Value> {
+        let (old, new) = match target {
+            Expr::Variable(name, depth) => {
+                let old = self.variable(name, depth)?;
+                let val = self.evaluate(expr)?;
+                let new = Self::operate(op, old.clone(), val)?;
+                self.assign(name, depth, new.clone())?;
+                (old, new)
+            }
+            Expr::Index(token, array, index) => {
+                let array_val = self.evaluate(array)?;
+                let index_val = self.evaluate(index)?;
+                let old = ops::index(array_val.clone(), index_val.clone())
+                    .map_err(|msg| Runtime::from_token(token, msg))?;
+                let val = self.evaluate(expr)?;
+                let new = Self::operate(op, old.clone(), val)?;
+                ops::set_index(array_val, index_val, new.clone())
+                    .map_err(|msg| Runtime::from_token(token, msg))?;
+                (old, new)
+            }
+            _ => panic!("Invalid update target: {:?}", target),
+        };
+        Ok(if postfix { old } else { new })
     }
 
     fn logical(&mut self, op: &Token, left: &Expr, right: &Expr) -> Result<Value> {
@@ -172,10 +582,10 @@ impl Interpreter {
 
         match op.val {
             TokenValue::And => Ok(Value::Bool(
-                Self::is_truthy(&left_val) && Self::is_truthy(&self.evaluate(right)?),
+                ops::is_truthy(&left_val) && ops::is_truthy(&self.evaluate(right)?),
             )),
             TokenValue::Or => Ok(Value::Bool(
-                Self::is_truthy(&left_val) || Self::is_truthy(&self.evaluate(right)?),
+                ops::is_truthy(&left_val) || ops::is_truthy(&self.evaluate(right)?),
             )),
             _ => Err(Runtime::from_token(op, "Unknown operation.")),
         }
@@ -184,44 +594,75 @@ impl Interpreter {
     fn binary(&mut self, op: &Token, left: &Expr, right: &Expr) -> Result<Value> {
         let left_val = self.evaluate(left)?;
         let right_val = self.evaluate(right)?;
+        Self::operate(op, left_val, right_val)
+    }
 
+    fn operate(op: &Token, left_val: Value, right_val: Value) -> Result<Value> {
         match op.val {
-            TokenValue::Plus => match (left_val, right_val) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
-                _ => Err(Runtime::from_token(op, "Operands must be two numbers.")),
-            },
-            TokenValue::Minus => match (left_val, right_val) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l - r)),
-                _ => Err(Runtime::from_token(op, "Operands must be two numbers.")),
-            },
-            TokenValue::Star => match (left_val, right_val) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l * r)),
-                _ => Err(Runtime::from_token(op, "Operands must be two numbers.")),
-            },
-            TokenValue::Slash => match (left_val, right_val) {
-                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l / r)),
-                _ => Err(Runtime::from_token(op, "Operands must be two numbers.")),
-            },
-            _ => Err(Runtime::from_token(op, "Unknown operation.")),
+            TokenValue::Plus => ops::add(left_val, right_val),
+            TokenValue::Minus => {
+                ops::arithmetic(left_val, right_val, i64::checked_sub, |l, r| l - r)
+            }
+            TokenValue::Star => {
+                ops::arithmetic(left_val, right_val, i64::checked_mul, |l, r| l * r)
+            }
+            TokenValue::Slash => ops::divide(left_val, right_val),
+            TokenValue::TildeSlash => ops::floor_divide(left_val, right_val),
+            TokenValue::Percent => ops::modulo(left_val, right_val),
+            TokenValue::StarStar => ops::power(left_val, right_val),
+            TokenValue::Ampersand => ops::bitwise(left_val, right_val, |l, r| l & r),
+            TokenValue::Pipe => ops::bitwise(left_val, right_val, |l, r| l | r),
+            TokenValue::Caret => ops::bitwise(left_val, right_val, |l, r| l ^ r),
+            TokenValue::LessLess => ops::shift(left_val, right_val, i64::wrapping_shl),
+            TokenValue::GreaterGreater => ops::shift(left_val, right_val, i64::wrapping_shr),
+            TokenValue::Less => ops::compare(left_val, right_val, Ordering::is_lt),
+            TokenValue::LessEqual => ops::compare(left_val, right_val, Ordering::is_le),
+            TokenValue::Greater => ops::compare(left_val, right_val, Ordering::is_gt),
+            TokenValue::GreaterEqual => ops::compare(left_val, right_val, Ordering::is_ge),
+            TokenValue::In => ops::contains(left_val, right_val),
+            TokenValue::EqualEqual => Ok(Value::Bool(left_val == right_val)),
+            TokenValue::BangEqual => Ok(Value::Bool(left_val != right_val)),
+            _ => Err("Unknown operation."),
         }
+        .map_err(|msg| Runtime::from_token(op, msg))
     }
 
     fn call(&mut self, token: &Token, callee: &Expr, args: &Vec<Expr>) -> Result<Value> {
         let func = self.evaluate(callee)?;
-        let Value::Function(parameters, statements, env) = func else {
-            return Err(Runtime::from_token(token, "Callee is not a function."));
-        };
-        if parameters.len() != args.len() {
+        let mut vals = vec![];
+        for a in args {
+            vals.push(self.evaluate(a)?);
+        }
+        self.call_value(token, &func, vals)
+    }
+
+    fn call_value(&mut self, token: &Token, func: &Value, args: Vec<Value>) -> Result<Value> {
+        match func {
+            Value::Function(func) => self.call_function(token, func, args),
+            Value::Native(native) => Self::call_native(token, native, args),
+            _ => Err(Runtime::from_token(token, "Callee is not a function.")),
+        }
+    }
+
+    fn call_function(&mut self, token: &Token, func: &Function, args: Vec<Value>) -> Result<Value> {
+        // 上限の深さか、ネイティブのスタックが溢れる前に止める
+        let here = stack_address();
+        if self.calls.is_empty() {
+            self.stack_base = here;
+        }
+        if self.calls.len() >= self.max_depth || self.stack_base.abs_diff(here) > self.stack_limit {
+            return Err(Runtime::from_token(token, "Stack overflow."));
+        }
+        if func.parameters.len() != args.len() {
             return Err(Runtime::from_token(
                 token,
                 "Number of the arguments does not match.",
             ));
         }
 
-        let closure = Environment::enclosed_by(&env);
+        let closure = Environment::enclosed_by(&func.env);
 
-        for (p, a) in parameters.iter().zip(args) {
-            let val = self.evaluate(a)?;
+        for (p, val) in func.parameters.iter().zip(args) {
             closure
                 .borrow_mut()
                 .define(p, val)
@@ -230,11 +671,15 @@ impl Interpreter {
 
         let previous = Rc::clone(&self.env);
         self.env = Environment::enclosed_by(&closure);
+        self.calls.push(Call {
+            function: func.name.clone(),
+            line: token.line,
+        });
 
         let mut result = Ok(Value::Null);
 
-        for statement in statements {
-            result = self.execute(&statement);
+        for statement in &func.statements {
+            result = self.execute(statement);
             if let Err(Runtime::Return(v)) = result {
                 result = Ok(v);
                 break;
@@ -244,50 +689,97 @@ impl Interpreter {
         }
 
         self.env = previous;
-        result
+        let call = self.calls.pop().expect("No call to return from.");
+        result.map_err(|runtime| runtime.called_from(call))
+    }
+
+    fn call_native(token: &Token, native: &Native, args: Vec<Value>) -> Result<Value> {
+        if native.arity != args.len() {
+            return Err(Runtime::from_token(
+                token,
+                "Number of the arguments does not match.",
+            ));
+        }
+
+        (native.func)(&args).map_err(|msg| Runtime::from_token(token, &msg))
     }
 
     fn unary(&mut self, op: &Token, right: &Expr) -> Result<Value> {
         let right_val = self.evaluate(right)?;
 
         match op.val {
-            TokenValue::Minus => match right_val {
-                Value::Number(r) => Ok(Value::Number(-r)),
-                _ => Err(Runtime::from_token(op, "Operand must be a number.")),
-            },
-            _ => Err(Runtime::from_token(op, "Unknown operation.")),
+            TokenValue::Minus => ops::negate(right_val),
+            TokenValue::Bang => Ok(Value::Bool(!ops::is_truthy(&right_val))),
+            TokenValue::Tilde => ops::bit_not(right_val),
+            _ => Err("Unknown operation."),
         }
+        .map_err(|msg| Runtime::from_token(op, msg))
     }
 
     fn ternary(&mut self, op: &Token, first: &Expr, second: &Expr, third: &Expr) -> Result<Value> {
         assert_eq!(op.val, TokenValue::Question);
 
         let condition = self.evaluate(first)?;
-        if Self::is_truthy(&condition) {
+        if ops::is_truthy(&condition) {
             self.evaluate(second)
         } else {
             self.evaluate(third)
         }
     }
 
-    fn variable(&self, name: &Token) -> Result<Value> {
-        self.env.borrow().get(name).map_err(Runtime::Error)
+    fn variable(&self, name: &Token, depth: &Depth) -> Result<Value> {
+        match depth {
+            Some(depth) => Environment::get_at(&self.env, *depth, name),
+            None => Environment::top_level(&self.env).borrow().get(name),
+        }
+        .map_err(Runtime::Error)
     }
 
-    fn function(&mut self, parameters: &[Token], statements: &[Stmt]) -> Result<Value> {
-        Ok(Value::Function(
-            parameters.to_owned(),
-            statements.to_owned(),
-            Rc::clone(&self.env),
-        ))
+    fn function(
+        &mut self,
+        name: Option<&Token>,
+        parameters: &[Token],
+        statements: &[Stmt],
+    ) -> Result<Value> {
+        Ok(Value::Function(gc::alloc(Function {
+            name: name.map(|name| name.lexeme.clone()),
+            parameters: parameters.to_owned(),
+            statements: statements.to_owned(),
+            env: Rc::clone(&self.env),
+        })))
     }
+}
 
-    fn is_truthy(val: &Value) -> bool {
-        match val {
-            Value::Bool(b) => *b,
-            Value::Number(n) => n != &0.0,
-            Value::Null | Value::Undefined => false,
-            _ => true,
-        }
+#[cfg(test)]
+mod test {
+    use super::Interpreter;
+    use crate::ast::Value;
+    use crate::error::Error;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn run(interpreter: &mut Interpreter, src: &str) -> Result<Value, Vec<Error>> {
+        let tokens = Scanner::new(src.to_string()).scan()?;
+        let statements = Parser::new(&tokens).parse()?;
+        let statements = interpreter.resolve(statements)?;
+        interpreter.interpret(&statements)
+    }
+
+    #[test]
+    fn test_define_native() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("twice", 1, |args| match args[0] {
+            Value::Int(n) => Ok(Value::Int(n * 2)),
+            _ => Err("Not a number.".to_string()),
+        });
+        assert_eq!(run(&mut interpreter, "twice(21);"), Ok(Value::Int(42)));
+        assert_eq!(
+            run(&mut interpreter, "twice(true);").map_err(|e| e[0].msg.clone()),
+            Err("Not a number.".to_string())
+        );
+        assert_eq!(
+            run(&mut interpreter, "twice(1, 2);").map_err(|e| e[0].msg.clone()),
+            Err("Number of the arguments does not match.".to_string())
+        );
     }
 }
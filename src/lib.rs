@@ -1,23 +1,45 @@
 pub mod ast;
+mod budget;
+mod builtins;
+mod chunk;
+mod compiler;
+pub mod dict;
 mod env;
 pub mod error;
+pub mod gc;
 mod interpreter;
+mod loader;
+mod ops;
 mod parser;
+mod resolver;
 mod scanner;
+pub mod session;
 pub mod token;
+mod vm;
 
 use ast::{Stmt, Value};
 use error::Error;
-use interpreter::Interpreter;
 use parser::Parser;
 use scanner::Scanner;
+use session::Session;
 use token::Token;
 
+// スクリプトの実行方式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Backend {
+    // 構文木をそのまま実行する
+    #[default]
+    TreeWalk,
+    // バイトコードにコンパイルしてVMで実行する
+    Vm,
+}
+
 pub fn run(src: impl Into<String>) -> Result<Value, Vec<Error>> {
-    Scanner::new(src.into())
-        .scan()
-        .and_then(|tokens| Parser::new(&tokens).parse())
-        .and_then(|stmts| Interpreter::new().interpret(&stmts))
+    run_with(src, Backend::default())
+}
+
+pub fn run_with(src: impl Into<String>, backend: Backend) -> Result<Value, Vec<Error>> {
+    Session::with_backend(backend).eval(src)
 }
 
 pub fn scan(src: impl Into<String>) -> Result<Vec<Token>, Vec<Error>> {
@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::ast::{Stmt, Value};
+use crate::error::Error;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+
+// モジュールのパスの解決、キャッシュと循環importの検出（インタプリタとVMで共有する）
+#[derive(Default)]
+pub struct Loader {
+    // 読み込み済みのモジュール（正規化したパスがキー）
+    modules: HashMap<PathBuf, Value>,
+    // 読み込み中のモジュール（正規化したパスと表示用のパス）
+    loading: Vec<(PathBuf, PathBuf)>,
+    // トップレベルのスクリプトからimportするときの基準のディレクトリ
+    base_dir: PathBuf,
+}
+
+pub enum Load {
+    Cached(Value),
+    Source(Source),
+}
+
+// これから実行するモジュール
+pub struct Source {
+    pub canonical: PathBuf,
+    // エラーメッセージに使うパス
+    pub file: String,
+    pub statements: Vec<Stmt>,
+    // VMがエラーの位置を切り出すソース
+    pub src: Rc<str>,
+    display: PathBuf,
+}
+
+impl Loader {
+    pub fn set_base_dir(&mut self, dir: PathBuf) {
+        self.base_dir = dir;
+    }
+
+    // builtinsはモジュールの外側にある変数の名前、atはimportの位置でのエラーを作る
+    pub fn load(
+        &mut self,
+        at: impl Fn(&str) -> Error,
+        path: &str,
+        builtins: HashSet<String>,
+    ) -> Result<Load, Error> {
+        // 相対パスはimportしているファイルからの位置とする
+        let base = match self.loading.last() {
+            Some((_, importer)) => importer.parent().unwrap_or(Path::new("")),
+            None => &self.base_dir,
+        };
+        let display = base.join(path);
+        let canonical = fs::canonicalize(&display).map_err(|e| {
+            at(&format!(
+                "Cannot open module '{}': {}.",
+                display.display(),
+                e
+            ))
+        })?;
+
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(Load::Cached(module.clone()));
+        }
+        if let Some(i) = self.loading.iter().position(|(p, _)| p == &canonical) {
+            let chain = self.loading[i..]
+                .iter()
+                .map(|(_, d)| d.display().to_string())
+                .chain([display.display().to_string()])
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(at(&format!("Import cycle detected: {}.", chain)));
+        }
+
+        let file = display.display().to_string();
+        let src = fs::read_to_string(&canonical)
+            .map_err(|e| at(&format!("Cannot read module '{}': {}.", file, e)))?;
+        let statements = Scanner::new(src.clone())
+            .scan()
+            .and_then(|tokens| Parser::new(&tokens).parse())
+            .and_then(|stmts| Resolver::new(HashSet::new(), builtins).resolve(stmts))
+            .map_err(|errors| Self::error(errors, &file))?;
+
+        Ok(Load::Source(Source {
+            canonical,
+            file,
+            statements,
+            src: src.into(),
+            display,
+        }))
+    }
+
+    // モジュールを実行している間に呼ぶ
+    pub fn enter(&mut self, source: &Source) {
+        self.loading
+            .push((source.canonical.clone(), source.display.clone()));
+    }
+
+    pub fn leave(&mut self) {
+        self.loading.pop();
+    }
+
+    pub fn cache(&mut self, source: Source, module: Value) {
+        self.modules.insert(source.canonical, module);
+    }
+
+    // モジュールの中で起きたエラー（すべてのエラーを1つにまとめて伝える）
+    pub fn error(errors: Vec<Error>, file: &str) -> Error {
+        let errors = errors.into_iter().map(|e| e.in_file(file)).collect();
+        Error::combine(errors).expect("No errors in module.")
+    }
+}
@@ -1,25 +1,246 @@
 // TODO: Option<Rc<Refcell>>を<Option<Box>>とOption::takeで書き直せるのでは？ → ダメぽい
 // TODO: ifやwhileをExprにするか、Exprが出てくるところをStmtにするかする
-// TODO: 比較演算子を作る
-// TODO: continueを作る
-// TODO: 配列を作る
-// TODO: 組み込み関数を作る
-// TODO: 文字列を作る
-// TODO: 辞書を作る
-// TODO: モジュールを作る
 
 use jasc::ast::Value;
-use std::io;
+use jasc::error::{Error, ErrorKind};
+use jasc::session::Session;
+use jasc::Backend;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
 
-fn main() {
-    let src = io::read_to_string(io::stdin()).expect("Error: failed to read the code.");
-    match jasc::run(src) {
-        Ok(Value::Null) => {}
-        Ok(value) => println!("{}", value),
+// 終了コードはsysexits.hにならう
+const EXIT_USAGE: u8 = 64;
+const EXIT_SYNTAX_ERROR: u8 = 65;
+const EXIT_NO_INPUT: u8 = 66;
+const EXIT_RUNTIME_ERROR: u8 = 70;
+
+const USAGE: &str =
+    "Usage: jasc [--repl] [--vm] [--errors plain|rich] [script.jsc | -e <code> | -] [args...]";
+
+enum Source {
+    Stdin,
+    File(String),
+    Inline(String),
+    Repl,
+}
+
+struct Options {
+    source: Source,
+    args: Vec<String>,
+    // エラーをソースの該当行つきで表示するか
+    rich: bool,
+    backend: Backend,
+}
+
+// 関数呼び出しの深さの上限まで再帰してもスタックが溢れないようにする
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+fn main() -> ExitCode {
+    let runner = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("Failed to start the interpreter thread.");
+    runner
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn run() -> ExitCode {
+    let Options {
+        source,
+        args,
+        rich,
+        backend,
+    } = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            eprintln!("{}", USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let mut session = Session::with_backend(backend);
+    session.set_stack_size(STACK_SIZE);
+    session.set("args", string_array(args));
+
+    let src = match source {
+        Source::Repl => {
+            repl(session, rich);
+            return ExitCode::SUCCESS;
+        }
+        Source::Stdin => match io::read_to_string(io::stdin()) {
+            Ok(src) => src,
+            Err(e) => {
+                eprintln!("Error: failed to read the code: {}", e);
+                return ExitCode::from(EXIT_NO_INPUT);
+            }
+        },
+        Source::File(path) => match std::fs::read_to_string(&path) {
+            Ok(src) => {
+                if let Some(dir) = Path::new(&path).parent() {
+                    session.set_base_dir(dir);
+                }
+                src
+            }
+            Err(e) => {
+                eprintln!("Error: failed to read '{}': {}", path, e);
+                return ExitCode::from(EXIT_NO_INPUT);
+            }
+        },
+        Source::Inline(src) => src,
+    };
+
+    // 構文エラーと実行時エラーで終了コードを分けるため、先に実行せずに調べる
+    if let Err(errors) = session.check(src.as_str()) {
+        report(&errors, &src, rich);
+        return ExitCode::from(EXIT_SYNTAX_ERROR);
+    }
+    if print_result(session.eval(src.as_str()), &src, rich) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_RUNTIME_ERROR)
+    }
+}
+
+// 実行するソースとスクリプトに渡す引数に分ける
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut repl = false;
+    let mut rich = io::stderr().is_terminal();
+    let mut backend = Backend::TreeWalk;
+    let mut rest = args.into_iter();
+
+    while let Some(arg) = rest.next() {
+        let source = match arg.as_str() {
+            "--repl" => {
+                repl = true;
+                continue;
+            }
+            "--vm" => {
+                backend = Backend::Vm;
+                continue;
+            }
+            "--errors" => {
+                rich = match rest.next().as_deref() {
+                    Some("plain") => false,
+                    Some("rich") => true,
+                    _ => return Err("--errors requires 'plain' or 'rich'.".to_string()),
+                };
+                continue;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-e" => match rest.next() {
+                Some(code) => Source::Inline(code),
+                None => return Err("-e requires an argument.".to_string()),
+            },
+            "-" => Source::Stdin,
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'.", arg)),
+            _ => Source::File(arg),
+        };
+        if repl {
+            return Err("--repl cannot be used with a script.".to_string());
+        }
+        return Ok(Options {
+            source,
+            args: rest.collect(),
+            rich,
+            backend,
+        });
+    }
+
+    let source = if repl || io::stdin().is_terminal() {
+        Source::Repl
+    } else {
+        Source::Stdin
+    };
+    Ok(Options {
+        source,
+        args: vec![],
+        rich,
+        backend,
+    })
+}
+
+fn string_array(strings: Vec<String>) -> Value {
+    Value::array(strings.into_iter().map(Value::String).collect())
+}
+
+fn report(errors: &[Error], src: &str, rich: bool) {
+    for e in errors {
+        if !rich {
+            e.report();
+            continue;
+        }
+        // モジュールの中のエラーはそのファイルのソースを示す
+        match &e.file {
+            None => e.report_with_source(src),
+            Some(file) => match std::fs::read_to_string(&**file) {
+                Ok(module_src) => e.report_with_source(&module_src),
+                Err(_) => e.report(),
+            },
+        }
+    }
+}
+
+// エラーがなければtrue
+fn print_result(result: Result<Value, Vec<Error>>, src: &str, rich: bool) -> bool {
+    match result {
+        Ok(Value::Null) => true,
+        Ok(value) => {
+            println!("{}", value);
+            true
+        }
         Err(errors) => {
-            for e in errors {
-                e.report();
+            report(&errors, src, rich);
+            false
+        }
+    }
+}
+
+fn repl(mut session: Session, rich: bool) {
+    let interactive = io::stdin().is_terminal();
+    let mut src = String::new();
+
+    loop {
+        if interactive {
+            print!("{}", if src.is_empty() { "> " } else { "... " });
+            io::stdout()
+                .flush()
+                .expect("Error: failed to write the prompt.");
+        }
+
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => src.push_str(&line),
+            Err(e) => {
+                eprintln!("Error: failed to read the code: {}", e);
+                break;
             }
         }
+
+        if !is_incomplete(&src) {
+            print_result(session.eval(src.as_str()), &src, rich);
+            src.clear();
+        }
+    }
+
+    // 入力が途中で終わった場合はエラーを表示する
+    if !src.trim().is_empty() {
+        print_result(session.eval(src.as_str()), &src, rich);
+    }
+}
+
+// 閉じていない括弧や文字列、コメントなど、入力の続きがあれば正しくなる場合
+fn is_incomplete(src: &str) -> bool {
+    match jasc::parse(src) {
+        Err(errors) => errors
+            .iter()
+            .any(|e| &*e.location == "end" || e.kind == ErrorKind::Unterminated),
+        Ok(_) => false,
     }
 }
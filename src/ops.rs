@@ -0,0 +1,283 @@
+// 値に対する演算（インタプリタとVMで共有する）
+// エラーの場合はメッセージを返し、位置は呼び出し側でつける
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+
+use crate::ast::Value;
+use crate::dict::Dict;
+use crate::gc;
+
+type Result<T> = std::result::Result<T, &'static str>;
+
+pub fn is_truthy(val: &Value) -> bool {
+    match val {
+        Value::Bool(b) => *b,
+        Value::Int(n) => *n != 0,
+        Value::Number(n) => n != &0.0,
+        Value::String(s) => !s.is_empty(),
+        Value::Null | Value::Undefined => false,
+        _ => true,
+    }
+}
+
+// 整数どうしなら整数のまま、どちらかが小数なら両方を小数にして計算する
+enum Numbers {
+    Int(i64, i64),
+    Float(f64, f64),
+}
+
+fn numbers(left: &Value, right: &Value) -> Option<Numbers> {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => Some(Numbers::Int(*l, *r)),
+        (Value::Int(l), Value::Number(r)) => Some(Numbers::Float(*l as f64, *r)),
+        (Value::Number(l), Value::Int(r)) => Some(Numbers::Float(*l, *r as f64)),
+        (Value::Number(l), Value::Number(r)) => Some(Numbers::Float(*l, *r)),
+        _ => None,
+    }
+}
+
+// 整数の演算で溢れたらエラーにする（多倍長整数にはしない）
+fn int(val: Option<i64>) -> Result<Value> {
+    val.map(Value::Int).ok_or("Integer overflow.")
+}
+
+pub fn add(left: Value, right: Value) -> Result<Value> {
+    match (left, right) {
+        (l @ Value::String(_), r) | (l, r @ Value::String(_)) => {
+            Ok(Value::String(format!("{}{}", l, r)))
+        }
+        (l, r) => arithmetic(l, r, i64::checked_add, |l, r| l + r),
+    }
+}
+
+pub fn arithmetic(
+    left: Value,
+    right: Value,
+    int_op: fn(i64, i64) -> Option<i64>,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Value> {
+    match numbers(&left, &right) {
+        Some(Numbers::Int(l, r)) => int(int_op(l, r)),
+        Some(Numbers::Float(l, r)) => Ok(Value::Number(float_op(l, r))),
+        None => Err("Operands must be two numbers."),
+    }
+}
+
+// `/`は整数どうしでも小数で割る
+pub fn divide(left: Value, right: Value) -> Result<Value> {
+    match numbers(&left, &right) {
+        Some(Numbers::Int(l, r)) => Ok(Value::Number(l as f64 / r as f64)),
+        Some(Numbers::Float(l, r)) => Ok(Value::Number(l / r)),
+        None => Err("Operands must be two numbers."),
+    }
+}
+
+// `~/`は小さい方に丸めた商、`%`はその余り（符号は右辺と同じになる）
+pub fn floor_divide(left: Value, right: Value) -> Result<Value> {
+    match numbers(&left, &right) {
+        Some(Numbers::Int(_, 0)) => Err("Division by zero."),
+        Some(Numbers::Int(l, r)) => {
+            let q = l.checked_div(r).ok_or("Integer overflow.")?;
+            let floor = q * r != l && (l < 0) != (r < 0);
+            Ok(Value::Int(if floor { q - 1 } else { q }))
+        }
+        Some(Numbers::Float(l, r)) => Ok(Value::Number((l / r).floor())),
+        None => Err("Operands must be two numbers."),
+    }
+}
+
+pub fn modulo(left: Value, right: Value) -> Result<Value> {
+    match numbers(&left, &right) {
+        Some(Numbers::Int(_, 0)) => Err("Division by zero."),
+        Some(Numbers::Int(l, r)) => {
+            // i64::MIN % -1だけは溢れるが、余りは0
+            let m = l.checked_rem(r).unwrap_or(0);
+            let floor = m != 0 && (m < 0) != (r < 0);
+            Ok(Value::Int(if floor { m + r } else { m }))
+        }
+        Some(Numbers::Float(l, r)) => Ok(Value::Number(l - r * (l / r).floor())),
+        None => Err("Operands must be two numbers."),
+    }
+}
+
+// 指数が負なら小数になる
+pub fn power(left: Value, right: Value) -> Result<Value> {
+    match numbers(&left, &right) {
+        Some(Numbers::Int(l, r)) if r >= 0 => {
+            int(u32::try_from(r).ok().and_then(|r| l.checked_pow(r)))
+        }
+        Some(Numbers::Int(l, r)) => Ok(Value::Number((l as f64).powf(r as f64))),
+        Some(Numbers::Float(l, r)) => Ok(Value::Number(l.powf(r))),
+        None => Err("Operands must be two numbers."),
+    }
+}
+
+// ビット演算は整数値の数だけに使える
+pub fn bitwise(left: Value, right: Value, f: fn(i64, i64) -> i64) -> Result<Value> {
+    match (exact_int(&left), exact_int(&right)) {
+        (Some(l), Some(r)) => Ok(Value::Int(f(l, r))),
+        _ => Err("Operands must be integers."),
+    }
+}
+
+// 左シフトであふれたビットは捨てる
+pub fn shift(left: Value, right: Value, f: fn(i64, u32) -> i64) -> Result<Value> {
+    match (exact_int(&left), exact_int(&right)) {
+        (Some(l), Some(r)) if (0..64).contains(&r) => Ok(Value::Int(f(l, r as u32))),
+        (Some(_), Some(_)) => Err("Shift amount out of range."),
+        _ => Err("Operands must be integers."),
+    }
+}
+
+pub fn bit_not(right: Value) -> Result<Value> {
+    match exact_int(&right) {
+        Some(r) => Ok(Value::Int(!r)),
+        None => Err("Operand must be an integer."),
+    }
+}
+
+// 整数と、整数値でi64に収まる小数を整数にする
+pub fn exact_int(val: &Value) -> Option<i64> {
+    match val {
+        Value::Int(n) => Some(*n),
+        // asで変換すると範囲外の値は丸められてしまう
+        Value::Number(n) if n.fract() == 0.0 && *n >= -(2f64.powi(63)) && *n < 2f64.powi(63) => {
+            Some(*n as i64)
+        }
+        _ => None,
+    }
+}
+
+pub fn compare(left: Value, right: Value, ordering: fn(Ordering) -> bool) -> Result<Value> {
+    let order = match (&left, &right) {
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        _ => match numbers(&left, &right) {
+            Some(Numbers::Int(l, r)) => Some(l.cmp(&r)),
+            Some(Numbers::Float(l, r)) => l.partial_cmp(&r),
+            None => return Err("Operands must be two numbers or two strings."),
+        },
+    };
+    // NaNとの比較は常にfalse
+    Ok(Value::Bool(order.is_some_and(ordering)))
+}
+
+// in演算子
+pub fn contains(left: Value, right: Value) -> Result<Value> {
+    match (left, right) {
+        (Value::String(key), Value::Dict(dict)) => {
+            Ok(Value::Bool(dict.borrow().contains_key(&key)))
+        }
+        (val, Value::Array(elements)) => Ok(Value::Bool(elements.borrow().contains(&val))),
+        (_, Value::Dict(_)) => Err("Key must be a string."),
+        _ => Err("Right operand must be an array or a dictionary."),
+    }
+}
+
+// for-inで回す要素（配列は回し始めたときの要素、辞書はキー）
+pub fn iterate(collection: Value) -> Result<Vec<Value>> {
+    match collection {
+        Value::Array(elements) => Ok(elements.borrow().clone()),
+        Value::Dict(dict) => Ok(dict
+            .borrow()
+            .iter()
+            .map(|(key, _)| Value::String(key.clone()))
+            .collect()),
+        _ => Err("Only arrays and dictionaries can be iterated."),
+    }
+}
+
+pub fn negate(right: Value) -> Result<Value> {
+    match right {
+        Value::Int(r) => int(r.checked_neg()),
+        Value::Number(r) => Ok(Value::Number(-r)),
+        _ => Err("Operand must be a number."),
+    }
+}
+
+pub fn array(elements: Vec<Value>) -> Value {
+    Value::Array(gc::alloc(RefCell::new(elements)))
+}
+
+pub fn dict(entries: impl IntoIterator<Item = (String, Value)>) -> Value {
+    Value::Dict(gc::alloc(RefCell::new(
+        entries.into_iter().collect::<Dict>(),
+    )))
+}
+
+pub fn index(container: Value, index: Value) -> Result<Value> {
+    match container {
+        Value::Array(elements) => {
+            let elements = elements.borrow();
+            let i = array_index(&index, elements.len())?;
+            Ok(elements[i].clone())
+        }
+        Value::Dict(dict) => {
+            let key = dict_key(index)?;
+            match dict.borrow().get(&key) {
+                Some(val) => Ok(val.clone()),
+                None => Err("Key not found."),
+            }
+        }
+        Value::Module(module) => {
+            let key = dict_key(index)?;
+            if !module.exports.contains(&key) {
+                return Err("Name not exported from module.");
+            }
+            match module.env.borrow().lookup(&key) {
+                Some(Value::Undefined) => Err("Variable not initialized."),
+                Some(val) => Ok(val.clone()),
+                None => Err("Exported name not defined."),
+            }
+        }
+        Value::Error(error) => match dict_key(index)?.as_str() {
+            "message" => Ok(Value::String(error.msg.clone())),
+            "line" => Ok(Value::Int(error.line as i64)),
+            _ => Err("Key not found."),
+        },
+        _ => Err("Only arrays and dictionaries can be indexed."),
+    }
+}
+
+pub fn set_index(container: Value, index: Value, val: Value) -> Result<Value> {
+    match container {
+        Value::Array(elements) => {
+            let mut elements = elements.borrow_mut();
+            let i = array_index(&index, elements.len())?;
+            elements[i] = val.clone();
+        }
+        Value::Dict(dict) => {
+            let key = dict_key(index)?;
+            dict.borrow_mut().insert(key, val.clone());
+        }
+        Value::Module(_) => return Err("Cannot assign to a module member."),
+        Value::Error(_) => return Err("Cannot assign to an error member."),
+        _ => return Err("Only arrays and dictionaries can be indexed."),
+    }
+    Ok(val)
+}
+
+fn array_index(index: &Value, len: usize) -> Result<usize> {
+    let n = match index {
+        Value::Int(n) => *n,
+        Value::Number(n) if n.fract() == 0.0 => *n as i64,
+        Value::Number(_) => return Err("Index must be an integer."),
+        _ => return Err("Index must be a number."),
+    };
+    if n < 0 || n >= len as i64 {
+        return Err("Index out of range.");
+    }
+    Ok(n as usize)
+}
+
+fn dict_key(key: Value) -> Result<String> {
+    match key {
+        Value::String(s) => Ok(s),
+        _ => Err("Key must be a string."),
+    }
+}
+
+// catchされずにトップレベルやモジュールの外まで届いた例外のメッセージ
+pub fn uncaught(val: &Value) -> String {
+    format!("Uncaught exception: {}", val)
+}
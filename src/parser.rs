@@ -12,7 +12,7 @@ pub struct Parser<'a> {
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 impl Parser<'_> {
-    pub fn new(tokens: &[Token]) -> Parser {
+    pub fn new(tokens: &[Token]) -> Parser<'_> {
         Parser { tokens, current: 0 }
     }
 
@@ -21,7 +21,7 @@ impl Parser<'_> {
         let mut errors = vec![];
 
         while !self.is_at_end() {
-            match self.statement() {
+            match self.declaration() {
                 Ok(stmt) => statements.push(stmt),
                 Err(error) => errors.push(error),
             }
@@ -33,21 +33,45 @@ impl Parser<'_> {
         }
     }
 
+    // exportはトップレベルでだけ使える
+    fn declaration(&mut self) -> Result<Stmt> {
+        match self.peek().val {
+            TokenValue::Export => self.export_statement().inspect_err(|_| self.synchronize()),
+            _ => self.statement(),
+        }
+    }
+
     fn statement(&mut self) -> Result<Stmt> {
         match self.peek().val {
-            TokenValue::LeftBrace => self.block_statement(),
+            TokenValue::Export => Err(Error::from_token(
+                self.peek(),
+                "Export is only allowed at top level.",
+            )),
+            TokenValue::LeftBrace if !self.is_dict_start() => self.block_statement(),
             TokenValue::Break => self.break_statement(),
+            TokenValue::Continue => self.continue_statement(),
+            TokenValue::For => self.for_statement(),
             TokenValue::If => self.if_statement(),
+            TokenValue::Import => self.import_statement(),
             TokenValue::Let => self.let_statement(),
             TokenValue::Print => self.print_statement(),
             TokenValue::Return => self.return_statement(),
+            TokenValue::Throw => self.throw_statement(),
+            TokenValue::Try => self.try_statement(),
             TokenValue::While => self.while_statement(),
             _ => self.expression_statement(),
         }
-        .map_err(|e| {
-            self.synchronize();
-            e
-        })
+        .inspect_err(|_| self.synchronize())
+    }
+
+    // 文頭の`{`は、`{ key: ...`の形のときだけ辞書とみなす
+    fn is_dict_start(&self) -> bool {
+        let key = self.tokens.get(self.current + 1).map(|t| &t.val);
+        let colon = self.tokens.get(self.current + 2).map(|t| &t.val);
+        matches!(
+            key,
+            Some(TokenValue::String(_)) | Some(TokenValue::Identifier)
+        ) && colon == Some(&TokenValue::Colon)
     }
 
     fn synchronize(&mut self) {
@@ -55,8 +79,14 @@ impl Parser<'_> {
             if self.previous().val == TokenValue::Semicolon {
                 return;
             }
-            if let TokenValue::If | TokenValue::Let | TokenValue::Print | TokenValue::While =
-                self.peek().val
+            if let TokenValue::If
+            | TokenValue::Let
+            | TokenValue::Print
+            | TokenValue::While
+            | TokenValue::For
+            | TokenValue::Try
+            | TokenValue::Import
+            | TokenValue::Export = self.peek().val
             {
                 return;
             }
@@ -90,6 +120,36 @@ impl Parser<'_> {
         Ok(Stmt::Break(token, expr))
     }
 
+    fn export_statement(&mut self) -> Result<Stmt> {
+        self.advance();
+        if !self.check(TokenValue::Let) {
+            return Err(Error::from_token(self.peek(), "Let expected after export."));
+        }
+        let statement = self.let_statement()?;
+        Ok(Stmt::Export(Box::new(statement)))
+    }
+
+    fn import_statement(&mut self) -> Result<Stmt> {
+        let token = self.advance().clone();
+        let path = match &self.peek().val {
+            TokenValue::String(path) => path.clone(),
+            _ => return Err(Error::from_token(self.peek(), "Module path expected.")),
+        };
+        self.advance();
+        self.consume(TokenValue::As, "'as' expected.")?;
+        let name = self
+            .consume(TokenValue::Identifier, "Identifier expected.")?
+            .clone();
+        self.consume(TokenValue::Semicolon, "Semicolon expected.")?;
+        Ok(Stmt::Import(token, path, name))
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt> {
+        let token = self.advance().clone();
+        self.consume(TokenValue::Semicolon, "Semicolon expected.")?;
+        Ok(Stmt::Continue(token))
+    }
+
     fn if_statement(&mut self) -> Result<Stmt> {
         self.advance();
         self.consume(TokenValue::LeftParen, "Left paren expected.")?;
@@ -109,10 +169,10 @@ impl Parser<'_> {
     }
 
     fn let_statement(&mut self) -> Result<Stmt> {
-        self.advance();
+        let token = self.advance().clone();
         let var = self.ternary()?;
-        let Expr::Variable(name) = var else {
-            return Err(Error::new(self.peek().line, "let", "Variable expected."));
+        let Expr::Variable(name, _) = var else {
+            return Err(Error::from_token(&token, "Variable expected."));
         };
         let mut expr = Expr::Literal(Value::Undefined);
         if self.check(TokenValue::Equal) {
@@ -120,7 +180,7 @@ impl Parser<'_> {
             expr = self.expression()?;
         }
         self.consume(TokenValue::Semicolon, "Initializer or semicolon expected.")?;
-        Ok(Stmt::Let(name, Box::new(expr)))
+        Ok(Stmt::Let(name, Box::new(expr), token.doc))
     }
 
     fn print_statement(&mut self) -> Result<Stmt> {
@@ -132,12 +192,52 @@ impl Parser<'_> {
 
     fn return_statement(&mut self) -> Result<Stmt> {
         let mut expr = None;
+        let token = self.peek().clone();
         self.advance();
         if self.peek().val != TokenValue::Semicolon {
             expr = Some(Box::new(self.expression()?));
         }
         self.consume(TokenValue::Semicolon, "Semicolon expected.")?;
-        Ok(Stmt::Return(expr))
+        Ok(Stmt::Return(token, expr))
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt> {
+        let token = self.advance().clone();
+        let expr = self.expression()?;
+        self.consume(TokenValue::Semicolon, "Semicolon expected.")?;
+        Ok(Stmt::Throw(token, Box::new(expr)))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt> {
+        let token = self.advance().clone();
+        let body = self.required_block()?;
+        let mut catch = None;
+        if self.check(TokenValue::Catch) {
+            self.advance();
+            self.consume(TokenValue::LeftParen, "Left paren expected.")?;
+            let name = self
+                .consume(TokenValue::Identifier, "Identifier expected.")?
+                .clone();
+            self.consume(TokenValue::RightParen, "Right paren expected.")?;
+            catch = Some((name, Box::new(self.required_block()?)));
+        }
+        let mut finally = None;
+        if self.check(TokenValue::Finally) {
+            self.advance();
+            finally = Some(Box::new(self.required_block()?));
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(Error::from_token(self.peek(), "Catch or finally expected."));
+        }
+        Ok(Stmt::Try(token, Box::new(body), catch, finally))
+    }
+
+    // tryやcatchの本体は必ずブロックにする
+    fn required_block(&mut self) -> Result<Stmt> {
+        if !self.check(TokenValue::LeftBrace) {
+            return Err(Error::from_token(self.peek(), "Left brace expected."));
+        }
+        self.block_statement()
     }
 
     fn while_statement(&mut self) -> Result<Stmt> {
@@ -149,6 +249,51 @@ impl Parser<'_> {
         Ok(Stmt::While(Box::new(condition), Box::new(statement)))
     }
 
+    fn for_statement(&mut self) -> Result<Stmt> {
+        self.advance();
+        self.consume(TokenValue::LeftParen, "Left paren expected.")?;
+        if self.check(TokenValue::Identifier)
+            && self.tokens.get(self.current + 1).map(|t| &t.val) == Some(&TokenValue::In)
+        {
+            return self.for_in_statement();
+        }
+
+        let init = match self.peek().val {
+            TokenValue::Semicolon => {
+                self.advance();
+                None
+            }
+            TokenValue::Let => Some(Box::new(self.let_statement()?)),
+            _ => Some(Box::new(self.expression_statement()?)),
+        };
+        let mut condition = None;
+        if !self.check(TokenValue::Semicolon) {
+            condition = Some(Box::new(self.expression()?));
+        }
+        self.consume(TokenValue::Semicolon, "Semicolon expected.")?;
+        let mut increment = None;
+        if !self.check(TokenValue::RightParen) {
+            increment = Some(Box::new(self.expression()?));
+        }
+        self.consume(TokenValue::RightParen, "Right paren expected.")?;
+        let statement = self.statement()?;
+        Ok(Stmt::For(init, condition, increment, Box::new(statement)))
+    }
+
+    fn for_in_statement(&mut self) -> Result<Stmt> {
+        let name = self.advance().clone();
+        let token = self.advance().clone();
+        let collection = self.expression()?;
+        self.consume(TokenValue::RightParen, "Right paren expected.")?;
+        let statement = self.statement()?;
+        Ok(Stmt::ForIn(
+            name,
+            token,
+            Box::new(collection),
+            Box::new(statement),
+        ))
+    }
+
     fn expression_statement(&mut self) -> Result<Stmt> {
         let expr = self.expression()?;
         self.consume(TokenValue::Semicolon, "Semicolon expected.")?;
@@ -160,16 +305,53 @@ impl Parser<'_> {
     }
 
     fn assignment(&mut self) -> Result<Expr> {
-        let var = self.ternary()?;
-        let Expr::Variable(ref token) = var else {
-            return Ok(var);
-        };
-        if !self.check(TokenValue::Equal) {
-            return Ok(var);
+        let target = self.ternary()?;
+        match self.peek().val {
+            TokenValue::Equal => {
+                let op = self.advance().clone();
+                let expr = self.assignment()?;
+                match target {
+                    Expr::Variable(token, depth) => {
+                        Ok(Expr::Assignment(token, Box::new(expr), depth))
+                    }
+                    Expr::Index(token, array, index) => {
+                        Ok(Expr::IndexAssignment(token, array, index, Box::new(expr)))
+                    }
+                    _ => Err(Error::from_token(&op, "Invalid assignment target.")),
+                }
+            }
+            TokenValue::PlusEqual
+            | TokenValue::MinusEqual
+            | TokenValue::StarEqual
+            | TokenValue::SlashEqual
+            | TokenValue::PercentEqual => {
+                let op = self.advance().clone();
+                let expr = self.assignment()?;
+                Self::update(op, target, expr, false)
+            }
+            _ => Ok(target),
+        }
+    }
+
+    // 対象の式は変数か添字の式だけ
+    fn update(op: Token, target: Expr, expr: Expr, postfix: bool) -> Result<Expr> {
+        if !matches!(target, Expr::Variable(..) | Expr::Index(..)) {
+            return Err(Error::from_token(&op, "Invalid assignment target."));
+        }
+        let val = match op.val {
+            TokenValue::PlusPlus | TokenValue::PlusEqual => TokenValue::Plus,
+            TokenValue::MinusMinus | TokenValue::MinusEqual => TokenValue::Minus,
+            TokenValue::StarEqual => TokenValue::Star,
+            TokenValue::SlashEqual => TokenValue::Slash,
+            TokenValue::PercentEqual => TokenValue::Percent,
+            _ => panic!("Unknown update operator: {:?}", op),
         };
-        self.advance();
-        let expr = self.assignment()?;
-        Ok(Expr::Assignment(token.clone(), Box::new(expr)))
+        Ok(Expr::Update(
+            Token { val, ..op },
+            Box::new(target),
+            Box::new(expr),
+            postfix,
+        ))
     }
 
     fn ternary(&mut self) -> Result<Expr> {
@@ -197,7 +379,50 @@ impl Parser<'_> {
     }
 
     fn and(&mut self) -> Result<Expr> {
-        self.binary(Self::term, &[TokenValue::And], Expr::Logical)
+        self.binary(Self::equality, &[TokenValue::And], Expr::Logical)
+    }
+
+    fn equality(&mut self) -> Result<Expr> {
+        self.binary(
+            Self::comparison,
+            &[TokenValue::EqualEqual, TokenValue::BangEqual],
+            Expr::Binary,
+        )
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        self.binary(
+            Self::bit_or,
+            &[
+                TokenValue::Less,
+                TokenValue::LessEqual,
+                TokenValue::Greater,
+                TokenValue::GreaterEqual,
+                TokenValue::In,
+            ],
+            Expr::Binary,
+        )
+    }
+
+    // ビット演算は比較より強く結びつける（`a & 1 == 0`は`(a & 1) == 0`）
+    fn bit_or(&mut self) -> Result<Expr> {
+        self.binary(Self::bit_xor, &[TokenValue::Pipe], Expr::Binary)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr> {
+        self.binary(Self::bit_and, &[TokenValue::Caret], Expr::Binary)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr> {
+        self.binary(Self::shift, &[TokenValue::Ampersand], Expr::Binary)
+    }
+
+    fn shift(&mut self) -> Result<Expr> {
+        self.binary(
+            Self::term,
+            &[TokenValue::LessLess, TokenValue::GreaterGreater],
+            Expr::Binary,
+        )
     }
 
     fn term(&mut self) -> Result<Expr> {
@@ -211,7 +436,12 @@ impl Parser<'_> {
     fn factor(&mut self) -> Result<Expr> {
         self.binary(
             Self::unary,
-            &[TokenValue::Star, TokenValue::Slash],
+            &[
+                TokenValue::Star,
+                TokenValue::Slash,
+                TokenValue::TildeSlash,
+                TokenValue::Percent,
+            ],
             Expr::Binary,
         )
     }
@@ -237,57 +467,125 @@ impl Parser<'_> {
 
     fn unary(&mut self) -> Result<Expr> {
         match self.peek().val {
-            TokenValue::Minus => {
+            TokenValue::Minus | TokenValue::Bang | TokenValue::Tilde => {
                 let op = self.advance().clone();
-                let right = self.primary()?;
+                let right = self.unary()?;
                 Ok(Expr::Unary(op, Box::new(right)))
             }
-            _ => self.call(),
+            _ => self.power(),
+        }
+    }
+
+    // 右結合で、単項演算子より強く結びつく（`-2 ** 2`は`-(2 ** 2)`、`2 ** -1`も書ける）
+    fn power(&mut self) -> Result<Expr> {
+        let left = self.increment()?;
+        if !self.check(TokenValue::StarStar) {
+            return Ok(left);
+        }
+        let op = self.advance().clone();
+        let right = self.unary()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    // 前置と後置の++/--
+    fn increment(&mut self) -> Result<Expr> {
+        let one = Expr::Literal(Value::Int(1));
+        if let TokenValue::PlusPlus | TokenValue::MinusMinus = self.peek().val {
+            let op = self.advance().clone();
+            let target = self.call()?;
+            return Self::update(op, target, one, false);
+        }
+        let target = self.call()?;
+        match self.peek().val {
+            TokenValue::PlusPlus | TokenValue::MinusMinus => {
+                let op = self.advance().clone();
+                Self::update(op, target, one, true)
+            }
+            _ => Ok(target),
         }
     }
 
     fn call(&mut self) -> Result<Expr> {
         let mut expr = self.primary()?;
 
-        while self.check(TokenValue::LeftParen) {
-            let token = self.peek().clone();
-            self.advance();
-            let args = self.arguments()?;
-            expr = Expr::Call(token, Box::new(expr), args);
+        loop {
+            match self.peek().val {
+                TokenValue::LeftParen => {
+                    let token = self.advance().clone();
+                    let args =
+                        self.expressions(TokenValue::RightParen, "No closing parenthesis.")?;
+                    expr = Expr::Call(token, Box::new(expr), args);
+                }
+                TokenValue::LeftBracket => {
+                    let token = self.advance().clone();
+                    let index = self.expression()?;
+                    self.consume(TokenValue::RightBracket, "Right bracket expected.")?;
+                    expr = Expr::Index(token, Box::new(expr), Box::new(index));
+                }
+                TokenValue::Dot => {
+                    let token = self.advance().clone();
+                    let name = self
+                        .consume(TokenValue::Identifier, "Property name expected.")?
+                        .lexeme
+                        .clone();
+                    let key = Expr::Literal(Value::String(name));
+                    expr = Expr::Index(token, Box::new(expr), Box::new(key));
+                }
+                _ => return Ok(expr),
+            }
         }
-        Ok(expr)
     }
 
-    fn arguments(&mut self) -> Result<Vec<Expr>> {
-        let mut args = vec![];
+    // 引数や配列の要素のように、カンマで区切られた式を閉じ括弧まで読む
+    fn expressions(&mut self, closing: TokenValue, msg: &str) -> Result<Vec<Expr>> {
+        let mut exprs = vec![];
 
-        while !self.check(TokenValue::RightParen) {
+        while !self.check(closing.clone()) {
             if self.is_at_end() {
-                return Err(Error::from_token(self.peek(), "No closing parenthesis."));
+                return Err(Error::from_token(self.peek(), msg));
             }
             let expr = self.expression()?;
-            args.push(expr);
-            if !self.check(TokenValue::RightParen) {
+            exprs.push(expr);
+            if !self.check(closing.clone()) {
                 self.consume(TokenValue::Comma, "Comma expected.")?;
             }
         }
 
         self.advance();
-        Ok(args)
+        Ok(exprs)
     }
 
     fn primary(&mut self) -> Result<Expr> {
+        // EOFの先までは読み進めない
+        if self.is_at_end() {
+            let token = self.peek();
+            return Err(Error::from_token(
+                token,
+                &format!("Expression expected, found `{}`", token.val),
+            ));
+        }
         let token = self.advance();
 
         match &token.val {
+            TokenValue::Int(n) => Ok(Expr::Literal(Value::Int(*n))),
             TokenValue::Number(n) => Ok(Expr::Literal(Value::Number(*n))),
+            TokenValue::String(s) => Ok(Expr::Literal(Value::String(s.clone()))),
             TokenValue::LeftParen => {
                 let expr = self.expression()?;
                 self.consume(TokenValue::RightParen, "Right paren expected")?;
                 Ok(Expr::Grouping(Box::new(expr)))
             }
+            TokenValue::LeftBracket => {
+                let token = token.clone();
+                let elements = self.expressions(TokenValue::RightBracket, "No closing bracket.")?;
+                Ok(Expr::Array(token, elements))
+            }
+            TokenValue::LeftBrace => {
+                let token = token.clone();
+                self.dict(token)
+            }
             TokenValue::Function => self.function(),
-            TokenValue::Identifier => Ok(Expr::Variable(token.clone())),
+            TokenValue::Identifier => Ok(Expr::Variable(token.clone(), None)),
             TokenValue::True => Ok(Expr::Literal(Value::Bool(true))),
             TokenValue::False => Ok(Expr::Literal(Value::Bool(false))),
             _ => Err(Error::from_token(
@@ -297,6 +595,31 @@ impl Parser<'_> {
         }
     }
 
+    fn dict(&mut self, token: Token) -> Result<Expr> {
+        let mut entries = vec![];
+
+        while !self.check(TokenValue::RightBrace) {
+            if self.is_at_end() {
+                return Err(Error::from_token(self.peek(), "No closing brace."));
+            }
+            let key = self.advance();
+            let key = match &key.val {
+                TokenValue::String(s) => s.clone(),
+                TokenValue::Identifier => key.lexeme.clone(),
+                _ => return Err(Error::from_token(key, "Key expected.")),
+            };
+            self.consume(TokenValue::Colon, "Colon expected.")?;
+            let val = self.expression()?;
+            entries.push((key, val));
+            if !self.check(TokenValue::RightBrace) {
+                self.consume(TokenValue::Comma, "Comma expected.")?;
+            }
+        }
+
+        self.advance();
+        Ok(Expr::Dict(token, entries))
+    }
+
     fn function(&mut self) -> Result<Expr> {
         self.consume(TokenValue::LeftParen, "Left parenthesis expected")?;
         let parameters = self.parameters()?;
@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Depth, Expr, Stmt};
+use crate::error::Error;
+use crate::token::{Span, Token};
+
+// 宣言した変数とその位置
+type Scope = HashMap<String, (usize, Span)>;
+
+// 実行前に変数の参照先の環境を調べ、スコープの誤りを報告する
+pub struct Resolver {
+    // ブロックと関数の環境（トップレベルは含まない）
+    scopes: Vec<Scope>,
+    // トップレベルでここまでに宣言された変数
+    globals: Scope,
+    // 以前の実行で定義済みのトップレベルの変数
+    defined: HashSet<String>,
+    // 組み込み関数など、トップレベルの外側にある変数
+    builtins: HashSet<String>,
+    // トップレベルで宣言されるすべての変数（関数の中からは宣言より前でも参照できる）
+    top_level: HashSet<String>,
+    functions: usize,
+    loops: usize,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    pub fn new(defined: HashSet<String>, builtins: HashSet<String>) -> Resolver {
+        Resolver {
+            scopes: vec![],
+            globals: HashMap::new(),
+            defined,
+            builtins,
+            top_level: HashSet::new(),
+            functions: 0,
+            loops: 0,
+            errors: vec![],
+        }
+    }
+
+    pub fn resolve(mut self, mut statements: Vec<Stmt>) -> Result<Vec<Stmt>, Vec<Error>> {
+        for statement in &statements {
+            Self::top_level_names(statement, &mut self.top_level);
+        }
+        for statement in &mut statements {
+            self.statement(statement);
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    // ブロックに入らずに実行されるletはトップレベルの環境に定義される
+    fn top_level_names(statement: &Stmt, names: &mut HashSet<String>) {
+        match statement {
+            Stmt::Export(statement) => Self::top_level_names(statement, names),
+            Stmt::If(_, consequence, alternative) => {
+                Self::top_level_names(consequence, names);
+                if let Some(alt) = alternative {
+                    Self::top_level_names(alt, names);
+                }
+            }
+            Stmt::Import(_, _, name) | Stmt::Let(name, _, _) => {
+                names.insert(name.lexeme.clone());
+            }
+            Stmt::While(_, statement) => Self::top_level_names(statement, names),
+            _ => {}
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Block(statements) => {
+                self.scopes.push(Scope::new());
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            Stmt::Break(token, expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr);
+                }
+                if self.loops == 0 {
+                    self.loop_error(token, "Break");
+                }
+            }
+            Stmt::Continue(token) => {
+                if self.loops == 0 {
+                    self.loop_error(token, "Continue");
+                }
+            }
+            Stmt::Export(statement) => self.statement(statement),
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.expression(expr),
+            // 初期化で宣言した変数は、条件や更新、本体から見えるスコープに入れる
+            Stmt::For(init, condition, increment, statement) => {
+                self.scopes.push(Scope::new());
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+                self.loops += 1;
+                self.statement(statement);
+                self.loops -= 1;
+                self.scopes.pop();
+            }
+            Stmt::ForIn(name, _, collection, statement) => {
+                self.expression(collection);
+                self.scopes.push(Scope::new());
+                self.declare(name);
+                self.loops += 1;
+                self.statement(statement);
+                self.loops -= 1;
+                self.scopes.pop();
+            }
+            Stmt::If(condition, consequence, alternative) => {
+                self.expression(condition);
+                self.statement(consequence);
+                if let Some(alt) = alternative {
+                    self.statement(alt);
+                }
+            }
+            Stmt::Import(_, _, name) => self.declare(name),
+            // 再帰呼び出しできるように、関数は本体より先に宣言する
+            Stmt::Let(name, expr, _) if matches!(**expr, Expr::Function(..)) => {
+                self.declare(name);
+                self.expression(expr);
+            }
+            Stmt::Let(name, expr, _) => {
+                self.expression(expr);
+                self.declare(name);
+            }
+            Stmt::Return(token, expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr);
+                }
+                if self.functions == 0 {
+                    self.errors
+                        .push(Error::from_token(token, "Return outside function."));
+                }
+            }
+            Stmt::Throw(_, expr) => self.expression(expr),
+            Stmt::Try(_, body, catch, finally) => {
+                self.statement(body);
+                // 例外を入れる変数のスコープの内側に本体のスコープを作る
+                if let Some((name, handler)) = catch {
+                    self.scopes.push(Scope::new());
+                    self.declare(name);
+                    self.statement(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.statement(finally);
+                }
+            }
+            Stmt::While(condition, statement) => {
+                self.expression(condition);
+                self.loops += 1;
+                self.statement(statement);
+                self.loops -= 1;
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Array(_, elements) => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Expr::Assignment(name, expr, depth) => {
+                self.expression(expr);
+                *depth = self.lookup(name);
+            }
+            Expr::Binary(_, left, right) | Expr::Logical(_, left, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call(_, callee, args) => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expr::Dict(_, entries) => {
+                for (_, val) in entries {
+                    self.expression(val);
+                }
+            }
+            Expr::Function(parameters, statements) => self.function(parameters, statements),
+            Expr::Grouping(expr) | Expr::Unary(_, expr) => self.expression(expr),
+            Expr::Index(_, array, index) => {
+                self.expression(array);
+                self.expression(index);
+            }
+            Expr::IndexAssignment(_, array, index, expr) => {
+                self.expression(array);
+                self.expression(index);
+                self.expression(expr);
+            }
+            Expr::Literal(_) => {}
+            Expr::Update(_, target, expr, _) => {
+                self.expression(target);
+                self.expression(expr);
+            }
+            Expr::Ternary(_, first, second, third) => {
+                self.expression(first);
+                self.expression(second);
+                self.expression(third);
+            }
+            Expr::Variable(name, depth) => *depth = self.lookup(name),
+        }
+    }
+
+    // 実行時と同じく、引数の環境の内側に本体の環境を作る
+    fn function(&mut self, parameters: &[Token], statements: &mut [Stmt]) {
+        let loops = std::mem::replace(&mut self.loops, 0);
+        self.functions += 1;
+
+        self.scopes.push(Scope::new());
+        for parameter in parameters {
+            self.declare(parameter);
+        }
+        self.scopes.push(Scope::new());
+        for statement in statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+        self.scopes.pop();
+
+        self.functions -= 1;
+        self.loops = loops;
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let defined_before = self.scopes.is_empty() && self.defined.contains(&name.lexeme);
+        let scope = self.scopes.last_mut().unwrap_or(&mut self.globals);
+
+        if let Some((line, span)) = scope.get(&name.lexeme) {
+            let error = Error::from_token(name, "Variable already defined.").with_note(
+                *line,
+                "Variable declared here.",
+                Some(*span),
+            );
+            self.errors.push(error);
+        } else if defined_before {
+            self.errors
+                .push(Error::from_token(name, "Variable already defined."));
+        } else {
+            scope.insert(name.lexeme.clone(), (name.line, name.span));
+        }
+    }
+
+    fn lookup(&mut self, name: &Token) -> Depth {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                return Some(depth);
+            }
+        }
+
+        let found = self.globals.contains_key(&name.lexeme)
+            || self.defined.contains(&name.lexeme)
+            || self.builtins.contains(&name.lexeme)
+            || (self.functions > 0 && self.top_level.contains(&name.lexeme));
+        if !found {
+            self.errors
+                .push(Error::from_token(name, "Variable not defined."));
+        }
+        None
+    }
+
+    fn loop_error(&mut self, token: &Token, statement: &str) {
+        let msg = if self.functions == 0 {
+            format!("{} from top level", statement)
+        } else {
+            format!("{} outside loop", statement)
+        };
+        self.errors.push(Error::from_token(token, &msg));
+    }
+}
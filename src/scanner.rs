@@ -1,24 +1,37 @@
-// TODO: コメントの処理がダサいのでなんとかする
-
-use crate::error::Error;
-use crate::token::{Token, TokenValue};
+use crate::error::{Error, ErrorKind};
+use crate::token::{Span, Token, TokenValue};
 
 #[derive(Debug)]
 pub struct Scanner {
     src: Vec<char>,
+    // 各文字のバイト単位の位置（最後にソースの長さを加えてある）
+    offsets: Vec<usize>,
     start: usize,
     current: usize,
+    start_line: usize,
     line: usize,
+    // 今の行とstartの行の先頭の位置（列を数えるのに使う）
+    line_start: usize,
+    start_line_start: usize,
+    // 次のletにつける`///`のコメント
+    doc: Vec<String>,
 }
 
 // TODO: multipeekを使う
 impl Scanner {
     pub fn new(src: String) -> Scanner {
+        let mut offsets: Vec<usize> = src.char_indices().map(|(i, _)| i).collect();
+        offsets.push(src.len());
         Scanner {
             src: src.chars().collect(),
+            offsets,
             start: 0,
             current: 0,
+            start_line: 1,
             line: 1,
+            line_start: 0,
+            start_line_start: 0,
+            doc: vec![],
         }
     }
 
@@ -26,17 +39,20 @@ impl Scanner {
         let mut tokens = vec![];
         let mut errors = vec![];
 
-        self.skip_whitespaces();
-        while !self.is_at_end() {
+        loop {
+            if let Err(error) = self.skip_trivia() {
+                errors.push(error);
+            }
+            if self.is_at_end() {
+                break;
+            }
             match self.scan_token() {
-                Ok(token) if token.val == TokenValue::Skip => (),
-                Ok(token) => tokens.push(token),
+                Ok(token) => tokens.push(self.attach_doc(token)),
                 Err(error) => errors.push(error),
             }
-            self.skip_whitespaces();
         }
 
-        self.start = self.current;
+        self.begin();
         tokens.push(self.make_token(TokenValue::EOF));
 
         if errors.is_empty() {
@@ -47,66 +63,334 @@ impl Scanner {
     }
 
     fn scan_token(&mut self) -> Result<Token, Error> {
-        self.start = self.current;
+        self.begin();
 
         match self.advance() {
             '(' => Ok(self.make_token(TokenValue::LeftParen)),
             ')' => Ok(self.make_token(TokenValue::RightParen)),
             '{' => Ok(self.make_token(TokenValue::LeftBrace)),
             '}' => Ok(self.make_token(TokenValue::RightBrace)),
-            '*' => Ok(self.make_token(TokenValue::Star)),
-            '/' => {
-                if self.peek() == '/' {
-                    self.advance();
-                    while !self.is_at_end() && self.peek() != '\n' {
-                        self.advance();
-                    }
-                    Ok(self.make_token(TokenValue::Skip))
-                } else {
-                    Ok(self.make_token(TokenValue::Slash))
-                }
+            '[' => Ok(self.make_token(TokenValue::LeftBracket)),
+            ']' => Ok(self.make_token(TokenValue::RightBracket)),
+            '*' => Ok(match self.peek() {
+                '*' => self.two_letter_op(TokenValue::StarStar),
+                '=' => self.two_letter_op(TokenValue::StarEqual),
+                _ => self.make_token(TokenValue::Star),
+            }),
+            '/' => Ok(self.one_or_two_letter_op('=', TokenValue::SlashEqual, TokenValue::Slash)),
+            // `//`は行コメントなので、切り捨ての割り算はDartと同じく`~/`にする
+            '~' => Ok(self.one_or_two_letter_op('/', TokenValue::TildeSlash, TokenValue::Tilde)),
+            '%' => {
+                Ok(self.one_or_two_letter_op('=', TokenValue::PercentEqual, TokenValue::Percent))
             }
-            '+' => Ok(self.make_token(TokenValue::Plus)),
-            '-' => Ok(self.make_token(TokenValue::Minus)),
+            '+' => Ok(match self.peek() {
+                '+' => self.two_letter_op(TokenValue::PlusPlus),
+                '=' => self.two_letter_op(TokenValue::PlusEqual),
+                _ => self.make_token(TokenValue::Plus),
+            }),
+            '-' => Ok(match self.peek() {
+                '-' => self.two_letter_op(TokenValue::MinusMinus),
+                '=' => self.two_letter_op(TokenValue::MinusEqual),
+                _ => self.make_token(TokenValue::Minus),
+            }),
             '?' => Ok(self.make_token(TokenValue::Question)),
             ':' => Ok(self.make_token(TokenValue::Colon)),
             ';' => Ok(self.make_token(TokenValue::Semicolon)),
-            '=' => Ok(self.make_token(TokenValue::Equal)),
+            '=' => Ok(self.one_or_two_letter_op('=', TokenValue::EqualEqual, TokenValue::Equal)),
+            '<' => Ok(match self.peek() {
+                '<' => self.two_letter_op(TokenValue::LessLess),
+                '=' => self.two_letter_op(TokenValue::LessEqual),
+                _ => self.make_token(TokenValue::Less),
+            }),
+            '>' => Ok(match self.peek() {
+                '>' => self.two_letter_op(TokenValue::GreaterGreater),
+                '=' => self.two_letter_op(TokenValue::GreaterEqual),
+                _ => self.make_token(TokenValue::Greater),
+            }),
             ',' => Ok(self.make_token(TokenValue::Comma)),
-            '&' => self.two_letter_op_or_error('&', TokenValue::And),
-            '|' => self.two_letter_op_or_error('|', TokenValue::Or),
-            c if c.is_ascii_digit() => Ok(self.number()),
+            '.' => Ok(self.make_token(TokenValue::Dot)),
+            '&' => Ok(self.one_or_two_letter_op('&', TokenValue::And, TokenValue::Ampersand)),
+            '|' => Ok(self.one_or_two_letter_op('|', TokenValue::Or, TokenValue::Pipe)),
+            '^' => Ok(self.make_token(TokenValue::Caret)),
+            '!' => Ok(self.one_or_two_letter_op('=', TokenValue::BangEqual, TokenValue::Bang)),
+            '"' => self.string(),
+            c if c.is_ascii_digit() => self.number(),
             c if Self::is_identifier_char(c) => Ok(self.identifier()),
-            c => Err(self.error(&c.to_string(), "Unexpected character.")),
+            c => Err(self.error(self.start, &c.to_string(), "Unexpected character.")),
         }
     }
 
-    fn skip_whitespaces(&mut self) {
-        while !self.is_at_end() && self.peek().is_whitespace() {
-            if self.peek() == '\n' {
-                self.line += 1
+    // 空白とコメントを読み飛ばす
+    fn skip_trivia(&mut self) -> Result<(), Error> {
+        loop {
+            match (self.peek(), self.peek_next()) {
+                (c, _) if c.is_whitespace() => {
+                    self.advance();
+                }
+                ('/', '/') => self.line_comment(),
+                ('/', '*') => self.block_comment()?,
+                _ => return Ok(()),
             }
+        }
+    }
+
+    // `///`で始まる行はドキュメントコメントとして取っておく（`////`は普通のコメント）
+    fn line_comment(&mut self) {
+        let start = self.current;
+        while !self.is_at_end() && self.peek() != '\n' {
             self.advance();
         }
+        let text = self.text(start);
+        if let Some(doc) = text.strip_prefix("///") {
+            if !doc.starts_with('/') {
+                let doc = doc.strip_prefix(' ').unwrap_or(doc);
+                self.doc.push(doc.trim_end().to_string());
+            }
+        }
     }
 
-    fn two_letter_op_or_error(&mut self, second: char, op: TokenValue) -> Result<Token, Error> {
+    // `/* */`は入れ子にできる
+    fn block_comment(&mut self) -> Result<(), Error> {
+        self.begin();
+        self.current += 2;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(self.unterminated("Unterminated comment."));
+            }
+            match (self.advance(), self.peek()) {
+                ('/', '*') => {
+                    self.advance();
+                    depth += 1;
+                }
+                ('*', '/') => {
+                    self.advance();
+                    depth -= 1;
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // ドキュメントコメントは直後のletにつけ、それ以外のトークンが来たら捨てる
+    // `export let`ではexportを飛ばしてletにつける
+    fn attach_doc(&mut self, token: Token) -> Token {
+        match token.val {
+            TokenValue::Export => token,
+            TokenValue::Let if !self.doc.is_empty() => Token {
+                doc: Some(std::mem::take(&mut self.doc).join("\n")),
+                ..token
+            },
+            _ => {
+                self.doc.clear();
+                token
+            }
+        }
+    }
+
+    fn one_or_two_letter_op(&mut self, second: char, two: TokenValue, one: TokenValue) -> Token {
         if self.peek() == second {
-            self.advance();
-            Ok(self.make_token(op))
+            self.two_letter_op(two)
         } else {
-            Err(self.error(
-                &self.peek().to_string(),
-                &format!("Expected '{}'.", &second),
-            ))
+            self.make_token(one)
+        }
+    }
+
+    // 2文字目を確かめた後に呼ぶ
+    fn two_letter_op(&mut self, op: TokenValue) -> Token {
+        self.advance();
+        self.make_token(op)
+    }
+
+    fn string(&mut self) -> Result<Token, Error> {
+        let mut s = String::new();
+        let mut error = None;
+
+        while !self.is_at_end() && self.peek() != '"' {
+            match self.advance() {
+                '\\' => match self.escape() {
+                    Ok(c) => s.push(c),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                },
+                c => s.push(c),
+            }
+        }
+
+        if self.is_at_end() {
+            return Err(self.unterminated("Unterminated string."));
+        }
+        self.advance();
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(self.make_token(TokenValue::String(s))),
+        }
+    }
+
+    // バックスラッシュの直後から呼ばれる
+    fn escape(&mut self) -> Result<char, Error> {
+        let start = self.current - 1;
+        if self.is_at_end() {
+            return Err(self.unterminated("Unterminated string."));
+        }
+
+        // 改行は文字列の一部として読み直す
+        if self.peek() == '\n' {
+            return Err(self.error(start, "\\", "Invalid escape sequence."));
+        }
+
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            'u' => self.unicode_escape(start),
+            _ => Err(self.error(start, &self.text(start), "Invalid escape sequence.")),
+        }
+    }
+
+    fn unicode_escape(&mut self, start: usize) -> Result<char, Error> {
+        if self.peek() != '{' {
+            return Err(self.error(start, &self.text(start), "Expected '{' in unicode escape."));
+        }
+        self.advance();
+
+        let digits = self.current;
+        while !self.is_at_end() && self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let hex = self.text(digits);
+        if self.peek() != '}' {
+            return Err(self.error(start, &self.text(start), "Expected '}' in unicode escape."));
+        }
+        self.advance();
+
+        if hex.is_empty() || hex.len() > 6 {
+            return Err(self.error(start, &self.text(start), "Invalid unicode escape."));
+        }
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(start, &self.text(start), "Invalid unicode escape."))
+    }
+
+    // 数字の間には区切りの`_`を書ける
+    fn number(&mut self) -> Result<Token, Error> {
+        if self.src[self.start] == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => Some((16, "Hexadecimal")),
+                'o' | 'O' => Some((8, "Octal")),
+                'b' | 'B' => Some((2, "Binary")),
+                _ => None,
+            };
+            if let Some((radix, name)) = radix {
+                self.advance();
+                return self.integer(radix, name);
+            }
+        }
+
+        self.digits(10);
+        let mut float = false;
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            float = true;
+            self.advance();
+            self.digits(10);
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            float = true;
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
+                self.advance();
+            }
+            if !self.peek().is_ascii_digit() {
+                self.skip_identifier_chars();
+                return Err(self.error(self.start, &self.lexeme(), "Exponent digits expected."));
+            }
+            self.digits(10);
+        }
+        if Self::is_identifier_char(self.peek()) {
+            self.skip_identifier_chars();
+            return Err(self.error(
+                self.start,
+                &self.lexeme(),
+                "Invalid character in number literal.",
+            ));
+        }
+
+        let text = self.separated_digits(10)?;
+        if float {
+            return Ok(self.make_token(TokenValue::Number(
+                text.parse().expect("Scanned number is not a float."),
+            )));
+        }
+        match text.parse() {
+            Ok(val) => Ok(self.make_token(TokenValue::Int(val))),
+            Err(_) => Err(self.too_large()),
+        }
+    }
+
+    // 0xなどの後の整数
+    fn integer(&mut self, radix: u32, name: &str) -> Result<Token, Error> {
+        self.digits(radix);
+        if Self::is_identifier_char(self.peek()) {
+            self.skip_identifier_chars();
+            return Err(self.error(
+                self.start,
+                &self.lexeme(),
+                &format!("Invalid digit in {} literal.", name.to_lowercase()),
+            ));
+        }
+        let text = self.separated_digits(radix)?;
+        if text.len() == 2 {
+            return Err(self.error(
+                self.start,
+                &self.lexeme(),
+                &format!("{} digits expected.", name),
+            ));
+        }
+        match i64::from_str_radix(&text[2..], radix) {
+            Ok(val) => Ok(self.make_token(TokenValue::Int(val))),
+            Err(_) => Err(self.too_large()),
+        }
+    }
+
+    fn too_large(&self) -> Error {
+        self.error(self.start, &self.lexeme(), "Integer literal too large.")
+    }
+
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
         }
     }
 
-    fn number(&mut self) -> Token {
-        while !self.is_at_end() && self.peek().is_ascii_digit() {
+    fn skip_identifier_chars(&mut self) {
+        while Self::is_identifier_char(self.peek()) {
             self.advance();
         }
-        self.make_token(TokenValue::Number(self.lexeme().parse().unwrap()))
+    }
+
+    // 区切りが数字の間にあることを確かめて、取り除いた字句を返す
+    fn separated_digits(&self, radix: u32) -> Result<String, Error> {
+        let chars = &self.src[self.start..self.current];
+        for (i, &c) in chars.iter().enumerate() {
+            let between = i > 0
+                && chars[i - 1].is_digit(radix)
+                && chars.get(i + 1).is_some_and(|c| c.is_digit(radix));
+            if c == '_' && !between {
+                return Err(self.error(
+                    self.start,
+                    &self.lexeme(),
+                    "Digit separator must be between digits.",
+                ));
+            }
+        }
+        Ok(chars.iter().filter(|&&c| c != '_').collect())
     }
 
     fn identifier(&mut self) -> Token {
@@ -118,13 +402,23 @@ impl Scanner {
             "function" => self.make_token(TokenValue::Function),
             "if" => self.make_token(TokenValue::If),
             "else" => self.make_token(TokenValue::Else),
+            "in" => self.make_token(TokenValue::In),
+            "import" => self.make_token(TokenValue::Import),
+            "export" => self.make_token(TokenValue::Export),
+            "as" => self.make_token(TokenValue::As),
             "let" => self.make_token(TokenValue::Let),
             "print" => self.make_token(TokenValue::Print),
             "return" => self.make_token(TokenValue::Return),
             "while" => self.make_token(TokenValue::While),
+            "for" => self.make_token(TokenValue::For),
             "true" => self.make_token(TokenValue::True),
             "false" => self.make_token(TokenValue::False),
             "break" => self.make_token(TokenValue::Break),
+            "continue" => self.make_token(TokenValue::Continue),
+            "try" => self.make_token(TokenValue::Try),
+            "catch" => self.make_token(TokenValue::Catch),
+            "finally" => self.make_token(TokenValue::Finally),
+            "throw" => self.make_token(TokenValue::Throw),
             _ => self.make_token(TokenValue::Identifier),
         }
     }
@@ -134,17 +428,53 @@ impl Scanner {
     }
 
     fn make_token(&self, val: TokenValue) -> Token {
-        Token::new(val, self.lexeme(), self.line)
+        Token::new(val, self.lexeme(), self.start_line).with_span(self.span(
+            self.start,
+            self.current,
+            self.start_line,
+        ))
+    }
+
+    // fromの文字がline行目（今の行かstartの行）、toの文字が今の行にあるとして範囲を作る
+    fn span(&self, from: usize, to: usize, line: usize) -> Span {
+        let from_line_start = if line == self.line {
+            self.line_start
+        } else {
+            self.start_line_start
+        };
+        Span {
+            start: self.offsets[from],
+            end: self.offsets[to],
+            column: from - from_line_start + 1,
+            end_line: self.line,
+            end_column: to - self.line_start + 1,
+        }
+    }
+
+    // トークンを読み始める
+    fn begin(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_line_start = self.line_start;
     }
 
     // self.currentの手前の文字までを切り出すことに注意
     fn lexeme(&self) -> String {
-        self.src[self.start..self.current].iter().collect()
+        self.text(self.start)
+    }
+
+    fn text(&self, start: usize) -> String {
+        self.src[start..self.current].iter().collect()
     }
 
     fn advance(&mut self) -> char {
+        let c = self.src[self.current];
         self.current += 1;
-        self.src[self.current - 1]
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.current;
+        }
+        c
     }
 
     fn is_at_end(&self) -> bool {
@@ -160,22 +490,57 @@ impl Scanner {
         }
     }
 
-    fn error(&self, location: &str, msg: &str) -> Error {
-        Error::new(self.line, if location == "\0" { "" } else { location }, msg)
+    fn peek_next(&self) -> char {
+        match self.src.get(self.current + 1) {
+            Some(&c) => c,
+            None => '\0',
+        }
+    }
+
+    // fromから今の位置までの範囲のエラー
+    fn error(&self, from: usize, location: &str, msg: &str) -> Error {
+        let location = if location == "\0" { "" } else { location };
+        let to = (from + location.chars().count()).min(self.src.len());
+        Error::new(self.line, location, msg).with_span(self.span(from, to, self.line))
+    }
+
+    // 閉じていない文字列などは始まった位置のエラーとする
+    fn unterminated(&self, msg: &str) -> Error {
+        Error::new(self.start_line, "", msg)
+            .with_kind(ErrorKind::Unterminated)
+            .with_span(self.span(self.start, self.current, self.start_line))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::Scanner;
-    use crate::error::Error;
-    use crate::token::{Token, TokenValue};
+    use crate::error::{Error, ErrorKind};
+    use crate::token::{Span, Token, TokenValue};
+
+    // 位置の範囲はtest_spanでテストする
+    fn scan(src: &str) -> Result<Vec<Token>, Vec<Error>> {
+        Scanner::new(src.to_string())
+            .scan()
+            .map(|tokens| {
+                tokens
+                    .into_iter()
+                    .map(|t| t.with_span(Span::default()))
+                    .collect()
+            })
+            .map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|e| Error { span: None, ..e })
+                    .collect()
+            })
+    }
 
     #[test]
     fn test_scanner() {
         let src =
             "function (a, b) { let a = (-1 + 2) / 3 * 4;\nif else return print while break a ? true : false && || ; }";
-        let result = Scanner::new(src.to_string()).scan();
+        let result = scan(src);
         let expected = vec![
             Token::new(TokenValue::Function, "function".to_string(), 1),
             Token::new(TokenValue::LeftParen, "(".to_string(), 1),
@@ -189,14 +554,14 @@ mod test {
             Token::new(TokenValue::Equal, "=".to_string(), 1),
             Token::new(TokenValue::LeftParen, "(".to_string(), 1),
             Token::new(TokenValue::Minus, "-".to_string(), 1),
-            Token::new(TokenValue::Number(1.0), "1".to_string(), 1),
+            Token::new(TokenValue::Int(1), "1".to_string(), 1),
             Token::new(TokenValue::Plus, "+".to_string(), 1),
-            Token::new(TokenValue::Number(2.0), "2".to_string(), 1),
+            Token::new(TokenValue::Int(2), "2".to_string(), 1),
             Token::new(TokenValue::RightParen, ")".to_string(), 1),
             Token::new(TokenValue::Slash, "/".to_string(), 1),
-            Token::new(TokenValue::Number(3.0), "3".to_string(), 1),
+            Token::new(TokenValue::Int(3), "3".to_string(), 1),
             Token::new(TokenValue::Star, "*".to_string(), 1),
-            Token::new(TokenValue::Number(4.0), "4".to_string(), 1),
+            Token::new(TokenValue::Int(4), "4".to_string(), 1),
             Token::new(TokenValue::Semicolon, ";".to_string(), 1),
             Token::new(TokenValue::If, "if".to_string(), 2),
             Token::new(TokenValue::Else, "else".to_string(), 2),
@@ -227,38 +592,168 @@ mod test {
     #[test]
     fn test_and() {
         assert_eq!(
-            Scanner::new("&&".to_string()).scan(),
+            scan("&&"),
             Ok(vec![
                 Token::new(TokenValue::And, "&&".to_string(), 1),
                 Token::new(TokenValue::EOF, "".to_string(), 1),
             ])
         );
         assert_eq!(
-            Scanner::new("&a".to_string()).scan(),
-            Err(vec![Error::new(1, "a", "Expected '&'.",)])
-        );
-        assert_eq!(
-            Scanner::new("&".to_string()).scan(),
-            Err(vec![Error::new(1, "", "Expected '&'.",)])
+            scan("&a"),
+            Ok(vec![
+                Token::new(TokenValue::Ampersand, "&".to_string(), 1),
+                Token::new(TokenValue::Identifier, "a".to_string(), 1),
+                Token::new(TokenValue::EOF, "".to_string(), 1),
+            ])
         );
     }
 
     #[test]
     fn test_or() {
         assert_eq!(
-            Scanner::new("||".to_string()).scan(),
+            scan("||"),
             Ok(vec![
                 Token::new(TokenValue::Or, "||".to_string(), 1),
                 Token::new(TokenValue::EOF, "".to_string(), 1),
             ])
         );
         assert_eq!(
-            Scanner::new("|a".to_string()).scan(),
-            Err(vec![Error::new(1, "a", "Expected '|'.",)])
+            scan("|a"),
+            Ok(vec![
+                Token::new(TokenValue::Pipe, "|".to_string(), 1),
+                Token::new(TokenValue::Identifier, "a".to_string(), 1),
+                Token::new(TokenValue::EOF, "".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_comparison() {
+        assert_eq!(
+            scan("= == != < <= > >="),
+            Ok(vec![
+                Token::new(TokenValue::Equal, "=".to_string(), 1),
+                Token::new(TokenValue::EqualEqual, "==".to_string(), 1),
+                Token::new(TokenValue::BangEqual, "!=".to_string(), 1),
+                Token::new(TokenValue::Less, "<".to_string(), 1),
+                Token::new(TokenValue::LessEqual, "<=".to_string(), 1),
+                Token::new(TokenValue::Greater, ">".to_string(), 1),
+                Token::new(TokenValue::GreaterEqual, ">=".to_string(), 1),
+                Token::new(TokenValue::EOF, "".to_string(), 1),
+            ])
+        );
+        assert_eq!(
+            scan("!a"),
+            Ok(vec![
+                Token::new(TokenValue::Bang, "!".to_string(), 1),
+                Token::new(TokenValue::Identifier, "a".to_string(), 1),
+                Token::new(TokenValue::EOF, "".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            scan("* ** / ~/ % ~ ^ << >> <<="),
+            Ok(vec![
+                Token::new(TokenValue::Star, "*".to_string(), 1),
+                Token::new(TokenValue::StarStar, "**".to_string(), 1),
+                Token::new(TokenValue::Slash, "/".to_string(), 1),
+                Token::new(TokenValue::TildeSlash, "~/".to_string(), 1),
+                Token::new(TokenValue::Percent, "%".to_string(), 1),
+                Token::new(TokenValue::Tilde, "~".to_string(), 1),
+                Token::new(TokenValue::Caret, "^".to_string(), 1),
+                Token::new(TokenValue::LessLess, "<<".to_string(), 1),
+                Token::new(TokenValue::GreaterGreater, ">>".to_string(), 1),
+                Token::new(TokenValue::LessLess, "<<".to_string(), 1),
+                Token::new(TokenValue::Equal, "=".to_string(), 1),
+                Token::new(TokenValue::EOF, "".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        assert_eq!(
+            scan("+ ++ += - -- -= *= /= %= +++"),
+            Ok(vec![
+                Token::new(TokenValue::Plus, "+".to_string(), 1),
+                Token::new(TokenValue::PlusPlus, "++".to_string(), 1),
+                Token::new(TokenValue::PlusEqual, "+=".to_string(), 1),
+                Token::new(TokenValue::Minus, "-".to_string(), 1),
+                Token::new(TokenValue::MinusMinus, "--".to_string(), 1),
+                Token::new(TokenValue::MinusEqual, "-=".to_string(), 1),
+                Token::new(TokenValue::StarEqual, "*=".to_string(), 1),
+                Token::new(TokenValue::SlashEqual, "/=".to_string(), 1),
+                Token::new(TokenValue::PercentEqual, "%=".to_string(), 1),
+                Token::new(TokenValue::PlusPlus, "++".to_string(), 1),
+                Token::new(TokenValue::Plus, "+".to_string(), 1),
+                Token::new(TokenValue::EOF, "".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_span() {
+        let tokens = Scanner::new("let a =\n  \"あ\nb\" + 1;".to_string())
+            .scan()
+            .unwrap();
+        let spans: Vec<(usize, Span)> = tokens.iter().map(|t| (t.line, t.span)).collect();
+        let span = |start, end, column, end_line, end_column| Span {
+            start,
+            end,
+            column,
+            end_line,
+            end_column,
+        };
+        assert_eq!(
+            spans,
+            vec![
+                (1, span(0, 3, 1, 1, 4)),
+                (1, span(4, 5, 5, 1, 6)),
+                (1, span(6, 7, 7, 1, 8)),
+                (2, span(10, 17, 3, 3, 3)),
+                (3, span(18, 19, 4, 3, 5)),
+                (3, span(20, 21, 6, 3, 7)),
+                (3, span(21, 22, 7, 3, 8)),
+                (3, span(22, 22, 8, 3, 8)),
+            ]
+        );
+        assert_eq!(
+            Scanner::new("1 @ 2".to_string()).scan(),
+            Err(vec![Error::new(1, "@", "Unexpected character.").with_span(
+                Span {
+                    start: 2,
+                    end: 3,
+                    column: 3,
+                    end_line: 1,
+                    end_column: 4
+                }
+            )])
+        );
+        assert_eq!(
+            Scanner::new("a\n  \"b".to_string()).scan(),
+            Err(vec![Error::new(2, "", "Unterminated string.")
+                .with_kind(ErrorKind::Unterminated)
+                .with_span(Span {
+                    start: 4,
+                    end: 6,
+                    column: 3,
+                    end_line: 2,
+                    end_column: 5
+                })])
         );
         assert_eq!(
-            Scanner::new("|".to_string()).scan(),
-            Err(vec![Error::new(1, "", "Expected '|'.",)])
+            Scanner::new("\"a\\\nb\";".to_string()).scan(),
+            Err(vec![Error::new(1, "\\", "Invalid escape sequence.")
+                .with_span(Span {
+                    start: 2,
+                    end: 3,
+                    column: 3,
+                    end_line: 1,
+                    end_column: 4
+                })])
         );
     }
 }
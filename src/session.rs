@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::ast::{Stmt, Value};
+use crate::budget::Budget;
+pub use crate::budget::InterruptHandle;
+use crate::error::Error;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::vm::Vm;
+use crate::Backend;
+
+// 関数呼び出しの深さの上限の既定値
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+// 実行するスレッドのスタックの大きさの既定値（Rustで作るスレッドの標準の大きさ）
+// 木を辿る実行方式は1段ごとにネイティブのスタックを数KB使うので、
+// 上限の深さに達する前でもスタックが溢れそうになったら"Stack overflow."にする
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+// ホストアプリケーションが状態を保ったままスクリプトを実行するためのもの
+pub struct Session {
+    engine: Engine,
+}
+
+enum Engine {
+    TreeWalk(Interpreter),
+    Vm(Vm),
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::with_backend(Backend::default())
+    }
+
+    pub fn with_backend(backend: Backend) -> Session {
+        let engine = match backend {
+            Backend::TreeWalk => Engine::TreeWalk(Interpreter::new()),
+            Backend::Vm => Engine::Vm(Vm::new()),
+        };
+        Session { engine }
+    }
+
+    pub fn eval(&mut self, src: impl Into<String>) -> Result<Value, Vec<Error>> {
+        let src = src.into();
+        Scanner::new(src.clone())
+            .scan()
+            .and_then(|tokens| Parser::new(&tokens).parse())
+            .and_then(|stmts| self.resolve(stmts))
+            .and_then(|stmts| {
+                self.budget().start().map_err(|e| vec![e])?;
+                match &mut self.engine {
+                    Engine::TreeWalk(interpreter) => interpreter.interpret(&stmts),
+                    Engine::Vm(vm) => vm.interpret(&stmts, &src),
+                }
+            })
+    }
+
+    // 実行せずに構文と変数のスコープだけを調べる
+    pub fn check(&self, src: impl Into<String>) -> Result<(), Vec<Error>> {
+        Scanner::new(src.into())
+            .scan()
+            .and_then(|tokens| Parser::new(&tokens).parse())
+            .and_then(|stmts| self.resolve(stmts))
+            .map(|_| ())
+    }
+
+    fn resolve(&self, statements: Vec<Stmt>) -> Result<Vec<Stmt>, Vec<Error>> {
+        match &self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.resolve(statements),
+            Engine::Vm(vm) => vm.resolve(statements),
+        }
+    }
+
+    // importの相対パスの基準（スクリプトファイルのディレクトリなど）
+    pub fn set_base_dir(&mut self, dir: impl Into<PathBuf>) {
+        match &mut self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.set_base_dir(dir.into()),
+            Engine::Vm(vm) => vm.set_base_dir(dir.into()),
+        }
+    }
+
+    // 関数呼び出しがこの深さを超えると"Stack overflow."のエラーにする
+    pub fn set_max_depth(&mut self, depth: usize) {
+        match &mut self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.set_max_depth(depth),
+            Engine::Vm(vm) => vm.set_max_depth(depth),
+        }
+    }
+
+    // 実行するスレッドのスタックの大きさ（VMはネイティブのスタックをほとんど使わない）
+    pub fn set_stack_size(&mut self, size: usize) {
+        if let Engine::TreeWalk(interpreter) = &mut self.engine {
+            interpreter.set_stack_size(size);
+        }
+    }
+
+    // evalやcallの1回で実行できるステップ数（Noneなら無制限）
+    // ステップの数え方は実行方式によって違う
+    pub fn set_max_steps(&mut self, steps: Option<u64>) {
+        self.budget().set_max_steps(steps);
+    }
+
+    // evalやcallの1回で実行できる時間（Noneなら無制限）
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.budget().set_timeout(timeout);
+    }
+
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        self.budget().interrupt_handle()
+    }
+
+    fn budget(&mut self) -> &mut Budget {
+        match &mut self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.budget(),
+            Engine::Vm(vm) => vm.budget(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match &self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.get_global(name),
+            Engine::Vm(vm) => vm.get_global(name),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, val: Value) {
+        match &mut self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.set_global(name, val),
+            Engine::Vm(vm) => vm.set_global(name, val),
+        }
+    }
+
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Vec<Error>> {
+        let Some(func) = self.get(name) else {
+            return Err(vec![Error::new(0, name, "Variable not defined.")]);
+        };
+        self.budget().start().map_err(|e| vec![e])?;
+        match &mut self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.call_from_host(name, &func, args),
+            Engine::Vm(vm) => vm.call_from_host(name, &func, args),
+        }
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        match &mut self.engine {
+            Engine::TreeWalk(interpreter) => interpreter.define_native(name, arity, func),
+            Engine::Vm(vm) => vm.define_native(name, arity, func),
+        }
+    }
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
@@ -1,82 +1,183 @@
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenValue {
-    Skip,
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Plus,
+    PlusPlus,
+    PlusEqual,
     Minus,
+    MinusMinus,
+    MinusEqual,
     Star,
+    StarStar,
+    StarEqual,
     Slash,
+    SlashEqual,
+    TildeSlash,
+    Percent,
+    PercentEqual,
+    Bang,
+    Tilde,
+    Ampersand,
+    Pipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
     And,
     Or,
     Question,
     Colon,
     Semicolon,
     Equal,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
     Comma,
+    Dot,
     Identifier,
+    Int(i64),
     Number(f64),
+    String(String),
     True,
     False,
     Function,
     If,
     Else,
+    In,
+    Import,
+    Export,
+    As,
     Let,
     Print,
     Return,
     While,
+    For,
     Break,
+    Continue,
+    Try,
+    Catch,
+    Finally,
+    Throw,
     EOF,
 }
 
 impl std::fmt::Display for TokenValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TokenValue::Skip => panic!("Invalid Token {:?}", TokenValue::Skip),
             TokenValue::LeftParen => write!(f, "("),
             TokenValue::RightParen => write!(f, ")"),
             TokenValue::LeftBrace => write!(f, "{{"),
             TokenValue::RightBrace => write!(f, "}}"),
+            TokenValue::LeftBracket => write!(f, "["),
+            TokenValue::RightBracket => write!(f, "]"),
             TokenValue::Plus => write!(f, "+"),
+            TokenValue::PlusPlus => write!(f, "++"),
+            TokenValue::PlusEqual => write!(f, "+="),
             TokenValue::Minus => write!(f, "-"),
+            TokenValue::MinusMinus => write!(f, "--"),
+            TokenValue::MinusEqual => write!(f, "-="),
             TokenValue::Star => write!(f, "*"),
+            TokenValue::StarStar => write!(f, "**"),
+            TokenValue::StarEqual => write!(f, "*="),
             TokenValue::Slash => write!(f, "/"),
+            TokenValue::SlashEqual => write!(f, "/="),
+            TokenValue::TildeSlash => write!(f, "~/"),
+            TokenValue::Percent => write!(f, "%"),
+            TokenValue::PercentEqual => write!(f, "%="),
+            TokenValue::Bang => write!(f, "!"),
+            TokenValue::Tilde => write!(f, "~"),
+            TokenValue::Ampersand => write!(f, "&"),
+            TokenValue::Pipe => write!(f, "|"),
+            TokenValue::Caret => write!(f, "^"),
+            TokenValue::LessLess => write!(f, "<<"),
+            TokenValue::GreaterGreater => write!(f, ">>"),
             TokenValue::And => write!(f, "&&"),
             TokenValue::Or => write!(f, "||"),
             TokenValue::Question => write!(f, "?"),
             TokenValue::Colon => write!(f, ":"),
             TokenValue::Semicolon => write!(f, ";"),
             TokenValue::Equal => write!(f, "="),
+            TokenValue::EqualEqual => write!(f, "=="),
+            TokenValue::BangEqual => write!(f, "!="),
+            TokenValue::Less => write!(f, "<"),
+            TokenValue::LessEqual => write!(f, "<="),
+            TokenValue::Greater => write!(f, ">"),
+            TokenValue::GreaterEqual => write!(f, ">="),
             TokenValue::Comma => write!(f, ","),
+            TokenValue::Dot => write!(f, "."),
             TokenValue::Identifier => write!(f, "id"),
+            TokenValue::Int(n) => write!(f, "{}", n),
             TokenValue::Number(n) => write!(f, "{}", n),
+            TokenValue::String(s) => write!(f, "{:?}", s),
             TokenValue::True => write!(f, "true"),
             TokenValue::False => write!(f, "false"),
             TokenValue::Function => write!(f, "function"),
             TokenValue::If => write!(f, "if"),
             TokenValue::Else => write!(f, "else"),
+            TokenValue::In => write!(f, "in"),
+            TokenValue::Import => write!(f, "import"),
+            TokenValue::Export => write!(f, "export"),
+            TokenValue::As => write!(f, "as"),
             TokenValue::Let => write!(f, "let"),
             TokenValue::Print => write!(f, "print"),
             TokenValue::Return => write!(f, "return"),
             TokenValue::While => write!(f, "while"),
+            TokenValue::For => write!(f, "for"),
             TokenValue::Break => write!(f, "while"),
+            TokenValue::Continue => write!(f, "continue"),
+            TokenValue::Try => write!(f, "try"),
+            TokenValue::Catch => write!(f, "catch"),
+            TokenValue::Finally => write!(f, "finally"),
+            TokenValue::Throw => write!(f, "throw"),
             TokenValue::EOF => write!(f, "end"),
         }
     }
 }
 
+// ソース上の範囲
+// startとendはバイト単位、columnとend_columnは文字単位で1から数える
+// endとend_columnは範囲の直後を指す
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub val: TokenValue,
     pub lexeme: String,
+    // トークンが始まる行
     pub line: usize,
+    pub span: Span,
+    // 直前の`///`のコメント（letのトークンだけが持つ）
+    pub doc: Option<String>,
 }
 
 impl Token {
     pub fn new(val: TokenValue, lexeme: String, line: usize) -> Token {
-        Token { val, lexeme, line }
+        Token {
+            val,
+            lexeme,
+            line,
+            span: Span::default(),
+            doc: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Token {
+        self.span = span;
+        self
     }
 }
 
@@ -0,0 +1,712 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::ast::{Module, Stmt, Value};
+use crate::budget::Budget;
+use crate::builtins;
+use crate::chunk::{Capture, Closure, Op, Upvalue};
+use crate::compiler::Compiler;
+use crate::env::Environment;
+use crate::error::{Call, Error};
+use crate::gc;
+use crate::loader::{Load, Loader};
+use crate::ops;
+use crate::resolver::Resolver;
+use crate::session::DEFAULT_MAX_DEPTH;
+use crate::token::{Token, TokenValue};
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // 最初の引数のスタック上の位置（その下に呼ばれた関数がある）
+    base: usize,
+}
+
+impl Frame {
+    // 実行中の命令の位置でのエラー
+    fn error(&self, msg: &str) -> Error {
+        self.closure.proto.chunk.error(self.ip - 1, msg)
+    }
+
+    // このフレームが呼んでいる関数から抜けるときの記録
+    fn call_to(&self, callee: &Frame) -> Call {
+        Call {
+            function: callee.closure.proto.name.clone(),
+            line: self.closure.proto.chunk.line(self.ip - 1),
+        }
+    }
+
+    fn name(&self, i: u16) -> &str {
+        match &self.closure.proto.chunk.constants[i as usize] {
+            Value::String(name) => name,
+            val => panic!("Variable name is not a string: {:?}", val),
+        }
+    }
+}
+
+// 例外のハンドラ
+struct Handler {
+    // 登録したrunの入れ子の深さ
+    run: usize,
+    // 登録したフレームの位置（呼び出し元のフレームの数）
+    frame: usize,
+    // 戻すスタックの高さ
+    height: usize,
+    target: usize,
+    // finally節を実行してから投げ直すためのもの
+    finally: bool,
+}
+
+// 実行を中断する例外（組み込みのエラーか、throwされた値）
+enum Exception {
+    Error(Error),
+    // 捕まえられなかったときのエラーと、投げられた値
+    // Resultが大きくなりすぎないようにエラーはBoxに入れる
+    Throw(Box<Error>, Value),
+}
+
+impl From<Error> for Exception {
+    fn from(error: Error) -> Exception {
+        Exception::Error(error)
+    }
+}
+
+impl Exception {
+    // catchで受け取る値
+    fn into_value(self) -> Value {
+        match self {
+            Exception::Error(error) => Value::Error(Rc::new(error)),
+            Exception::Throw(_, val) => val,
+        }
+    }
+
+    fn into_error(self) -> Error {
+        match self {
+            Exception::Error(error) => error,
+            Exception::Throw(error, _) => *error,
+        }
+    }
+
+    fn called_from(self, call: Call) -> Exception {
+        match self {
+            Exception::Error(error) => Exception::Error(error.called_from(call)),
+            Exception::Throw(error, val) => {
+                Exception::Throw(Box::new(error.called_from(call)), val)
+            }
+        }
+    }
+}
+
+// バイトコードを実行するスタックマシン
+pub struct Vm {
+    // 組み込み関数の入った、すべてのスクリプトとモジュールの外側の環境
+    globals: Rc<RefCell<Environment>>,
+    // スクリプトのトップレベルの環境
+    env: Rc<RefCell<Environment>>,
+    stack: Vec<Value>,
+    // 呼び出し元の関数（実行中の関数はrunのローカル変数）
+    frames: Vec<Frame>,
+    // まだスタック上にある変数を指すアップバリュー（位置の昇順）
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    handlers: Vec<Handler>,
+    // 入れ子になって実行中のrunの数
+    runs: usize,
+    // finally節を実行している間、投げ直すまで預かる例外
+    pending: Vec<Exception>,
+    loader: Loader,
+    // 実行中のモジュールでexportされた変数
+    exports: Vec<String>,
+    max_depth: usize,
+    budget: Budget,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let globals = Rc::new(RefCell::new(Environment::default()));
+        builtins::define(&mut globals.borrow_mut());
+        Vm {
+            env: Environment::enclosed_by(&globals),
+            globals,
+            stack: vec![],
+            frames: vec![],
+            open_upvalues: vec![],
+            handlers: vec![],
+            runs: 0,
+            pending: vec![],
+            loader: Loader::default(),
+            exports: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
+            budget: Budget::default(),
+        }
+    }
+
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        self.globals
+            .borrow_mut()
+            .set(name, Value::native(name, arity, func));
+    }
+
+    // これまでに定義された変数を踏まえて、変数の参照先を調べる
+    pub fn resolve(&self, statements: Vec<Stmt>) -> Result<Vec<Stmt>, Vec<Error>> {
+        let defined = self.env.borrow().names().cloned().collect();
+        Resolver::new(defined, self.builtin_names()).resolve(statements)
+    }
+
+    fn builtin_names(&self) -> HashSet<String> {
+        self.globals.borrow().names().cloned().collect()
+    }
+
+    // srcはstatementsのスパンが指すソース（エラーの位置に使う）
+    pub fn interpret(&mut self, statements: &[Stmt], src: &str) -> Result<Value, Vec<Error>> {
+        let script = self.script(statements, src.into(), &self.env.clone())?;
+        self.call_value(script, vec![], None)
+            .map_err(Error::into_all)
+    }
+
+    fn script(
+        &self,
+        statements: &[Stmt],
+        src: Rc<str>,
+        env: &Rc<RefCell<Environment>>,
+    ) -> Result<Value, Vec<Error>> {
+        let proto = Compiler::compile(statements, src)?;
+        Ok(Value::Closure(gc::alloc(Closure {
+            proto: Rc::new(proto),
+            upvalues: vec![],
+            globals: Rc::clone(env),
+        })))
+    }
+
+    // ホストアプリケーションから関数を呼ぶ
+    pub fn call_from_host(
+        &mut self,
+        name: &str,
+        func: &Value,
+        args: Vec<Value>,
+    ) -> Result<Value, Vec<Error>> {
+        let token = Token::new(TokenValue::Identifier, name.to_string(), 0);
+        // ソース上に呼び出し位置はない
+        self.call_value(func.clone(), args, Some(&token))
+            .map_err(|e| match e.line {
+                0 => Error { span: None, ..e }.into_all(),
+                _ => e.into_all(),
+            })
+    }
+
+    pub fn set_base_dir(&mut self, dir: PathBuf) {
+        self.loader.set_base_dir(dir);
+    }
+
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    pub(crate) fn budget(&mut self) -> &mut Budget {
+        &mut self.budget
+    }
+
+    // スクリプトで定義された変数、組み込み関数の順に探す
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env.borrow().find(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, val: Value) {
+        self.env.borrow_mut().set(name, val)
+    }
+
+    // 関数を呼んで、戻るまで実行する
+    // tokenは関数でない値や引数の数の誤りを報告する位置（Noneなら実行中の命令）
+    fn call_value(
+        &mut self,
+        func: Value,
+        args: Vec<Value>,
+        token: Option<&Token>,
+    ) -> Result<Value, Error> {
+        let height = self.stack.len();
+        let depth = self.frames.len();
+        let pending = self.pending.len();
+        let argc = args.len();
+        self.stack.push(func);
+        self.stack.extend(args);
+
+        let result = match self.call(argc) {
+            Ok(Some(frame)) => {
+                // ホストアプリケーションから呼んだ関数も記録する
+                let call = token.map(|token| Call {
+                    function: frame.closure.proto.name.clone(),
+                    line: token.line,
+                });
+                self.frames.push(frame);
+                self.run(depth).map_err(|exception| match call {
+                    Some(call) => exception.called_from(call).into_error(),
+                    None => exception.into_error(),
+                })
+            }
+            Ok(None) => Ok(self.pop()),
+            Err(msg) => Err(match token {
+                Some(token) => Error::from_token(token, &msg),
+                None => Error::new(0, "", &msg),
+            }),
+        };
+
+        if result.is_err() {
+            self.close_upvalues(height);
+            self.stack.truncate(height);
+            self.frames.truncate(depth);
+        }
+        // finally節から抜け出して投げ直されなかった例外も捨てる
+        self.pending.truncate(pending);
+        result
+    }
+
+    // スタック上の関数を呼ぶ（ネイティブ関数はその場で実行して結果を積む）
+    fn call(&mut self, argc: usize) -> Result<Option<Frame>, String> {
+        let base = self.stack.len() - argc;
+        match &self.stack[base - 1] {
+            Value::Closure(closure) => {
+                if closure.proto.parameters.len() != argc {
+                    return Err("Number of the arguments does not match.".to_string());
+                }
+                Ok(Some(Frame {
+                    closure: Rc::clone(closure),
+                    ip: 0,
+                    base,
+                }))
+            }
+            Value::Native(native) => {
+                if native.arity != argc {
+                    return Err("Number of the arguments does not match.".to_string());
+                }
+                let native = Rc::clone(native);
+                let args = self.stack.split_off(base);
+                self.stack.pop();
+                self.stack.push((native.func)(&args)?);
+                Ok(None)
+            }
+            _ => Err("Callee is not a function.".to_string()),
+        }
+    }
+
+    // depth番目のフレームから戻るまで実行する
+    fn run(&mut self, depth: usize) -> Result<Value, Exception> {
+        let mut frame = self.frames.pop().expect("No frame to run.");
+        self.runs += 1;
+        let result = loop {
+            let exception = match self.execute(&mut frame, depth) {
+                Ok(val) => break Ok(val),
+                Err(exception) => exception,
+            };
+            if let Err(exception) = self.unwind(&mut frame, depth, exception) {
+                break Err(exception);
+            }
+        };
+        self.runs -= 1;
+        result
+    }
+
+    // 例外を受け取るハンドラまでフレームとスタックを戻す
+    // このrunの中で登録されたハンドラがなければ、呼び出し元に伝える
+    // （モジュールの読み込みなどの入れ子のrunでは、実行中だった呼び出し元のフレームがframesに
+    // 入っていないので、フレームの数ではどのrunのハンドラか見分けられない）
+    fn unwind(
+        &mut self,
+        frame: &mut Frame,
+        depth: usize,
+        mut exception: Exception,
+    ) -> Result<(), Exception> {
+        // 実行の打ち切りはハンドラに渡さない
+        if matches!(&exception, Exception::Error(error) if error.kind.is_abort()) {
+            while self
+                .handlers
+                .last()
+                .is_some_and(|handler| handler.run == self.runs)
+            {
+                self.handlers.pop();
+            }
+        }
+        let handler = match self.handlers.last() {
+            Some(handler) if handler.run == self.runs => Some(handler.frame),
+            _ => None,
+        };
+
+        // 抜けるフレームの呼び出しを記録する
+        while self.frames.len() > handler.unwrap_or(depth) {
+            let caller = self.frames.pop().expect("No frame to unwind to.");
+            exception = exception.called_from(caller.call_to(frame));
+            *frame = caller;
+        }
+        if handler.is_none() {
+            return Err(exception);
+        }
+        let handler = self.handlers.pop().expect("No handler.");
+        self.close_upvalues(handler.height);
+        self.stack.truncate(handler.height);
+        if handler.finally {
+            // 例外はスクリプトから見えないので、預けた位置を積んでおく
+            self.stack.push(Value::Int(self.pending.len() as i64));
+            self.pending.push(exception);
+        } else {
+            self.stack.push(exception.into_value());
+        }
+        frame.ip = handler.target;
+        Ok(())
+    }
+
+    fn execute(&mut self, frame: &mut Frame, depth: usize) -> Result<Value, Exception> {
+        loop {
+            self.budget.step()?;
+            let op = frame.closure.proto.chunk.code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Constant(i) => {
+                    let val = frame.closure.proto.chunk.constants[i as usize].clone();
+                    self.stack.push(val);
+                }
+                Op::Null => self.stack.push(Value::Null),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Dup(n) => {
+                    let val = self.stack[self.stack.len() - 1 - n as usize].clone();
+                    self.stack.push(val);
+                }
+                Op::PopLocals(n) => self.pop_locals(n as usize),
+                Op::CloseUpvalues(i) => self.close_upvalues(frame.base + i as usize),
+                Op::PopUnder(n) => {
+                    let top = self.pop();
+                    self.pop_locals(n as usize);
+                    self.stack.push(top);
+                }
+                Op::GetLocal(i) => {
+                    let val = self.stack[frame.base + i as usize].clone();
+                    self.push_initialized(frame, val)?;
+                }
+                Op::SetLocal(i) => {
+                    let val = self.peek().clone();
+                    self.stack[frame.base + i as usize] = val;
+                }
+                Op::DefineLocal(i) => {
+                    let slot = frame.base + i as usize;
+                    if self.stack[slot] != Value::Undefined {
+                        return Err(frame.error("Variable already defined.").into());
+                    }
+                    self.stack[slot] = self.pop();
+                }
+                Op::GetUpvalue(i) => {
+                    let val = match &*frame.closure.upvalues[i as usize].borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(val) => val.clone(),
+                    };
+                    self.push_initialized(frame, val)?;
+                }
+                Op::SetUpvalue(i) => {
+                    let val = self.peek().clone();
+                    match &mut *frame.closure.upvalues[i as usize].borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = val,
+                        Upvalue::Closed(closed) => *closed = val,
+                    }
+                }
+                Op::DefineGlobal(i) => {
+                    let val = self.pop();
+                    if !frame
+                        .closure
+                        .globals
+                        .borrow_mut()
+                        .declare(frame.name(i), val)
+                    {
+                        return Err(frame.error("Variable already defined.").into());
+                    }
+                }
+                Op::GetGlobal(i) => {
+                    let val = frame.closure.globals.borrow().find(frame.name(i));
+                    match val {
+                        Some(val) => self.push_initialized(frame, val)?,
+                        None => return Err(frame.error("Variable not defined.").into()),
+                    }
+                }
+                Op::SetGlobal(i) => {
+                    let val = self.peek().clone();
+                    if !frame
+                        .closure
+                        .globals
+                        .borrow_mut()
+                        .replace(frame.name(i), val)
+                    {
+                        return Err(frame.error("Variable not defined.").into());
+                    }
+                }
+                Op::Export(i) => self.exports.push(frame.name(i).to_string()),
+                Op::Import(i) => {
+                    let module = self.load_module(frame, frame.name(i))?;
+                    self.stack.push(module);
+                }
+                Op::Array(n) => {
+                    let elements = self.stack.split_off(self.stack.len() - n as usize);
+                    self.stack.push(ops::array(elements));
+                }
+                Op::Dict(n) => {
+                    let entries = self.stack.split_off(self.stack.len() - 2 * n as usize);
+                    let mut pairs = vec![];
+                    let mut entries = entries.into_iter();
+                    while let (Some(Value::String(key)), Some(val)) =
+                        (entries.next(), entries.next())
+                    {
+                        pairs.push((key, val));
+                    }
+                    self.stack.push(ops::dict(pairs));
+                }
+                Op::Index => {
+                    let index = self.pop();
+                    let container = self.pop();
+                    let val = ops::index(container, index).map_err(|msg| frame.error(msg))?;
+                    self.stack.push(val);
+                }
+                Op::SetIndex => {
+                    let val = self.pop();
+                    let index = self.pop();
+                    let container = self.pop();
+                    let val =
+                        ops::set_index(container, index, val).map_err(|msg| frame.error(msg))?;
+                    self.stack.push(val);
+                }
+                Op::Add => self.binary(frame, ops::add)?,
+                Op::Subtract => self.binary(frame, |l, r| {
+                    ops::arithmetic(l, r, i64::checked_sub, |l, r| l - r)
+                })?,
+                Op::Multiply => self.binary(frame, |l, r| {
+                    ops::arithmetic(l, r, i64::checked_mul, |l, r| l * r)
+                })?,
+                Op::Divide => self.binary(frame, ops::divide)?,
+                Op::FloorDivide => self.binary(frame, ops::floor_divide)?,
+                Op::Modulo => self.binary(frame, ops::modulo)?,
+                Op::Power => self.binary(frame, ops::power)?,
+                Op::BitAnd => self.binary(frame, |l, r| ops::bitwise(l, r, |l, r| l & r))?,
+                Op::BitOr => self.binary(frame, |l, r| ops::bitwise(l, r, |l, r| l | r))?,
+                Op::BitXor => self.binary(frame, |l, r| ops::bitwise(l, r, |l, r| l ^ r))?,
+                Op::ShiftLeft => self.binary(frame, |l, r| ops::shift(l, r, i64::wrapping_shl))?,
+                Op::ShiftRight => self.binary(frame, |l, r| ops::shift(l, r, i64::wrapping_shr))?,
+                Op::Less => self.binary(frame, |l, r| ops::compare(l, r, Ordering::is_lt))?,
+                Op::LessEqual => self.binary(frame, |l, r| ops::compare(l, r, Ordering::is_le))?,
+                Op::Greater => self.binary(frame, |l, r| ops::compare(l, r, Ordering::is_gt))?,
+                Op::GreaterEqual => {
+                    self.binary(frame, |l, r| ops::compare(l, r, Ordering::is_ge))?
+                }
+                Op::Equal => self.binary(frame, |l, r| Ok(Value::Bool(l == r)))?,
+                Op::NotEqual => self.binary(frame, |l, r| Ok(Value::Bool(l != r)))?,
+                Op::In => self.binary(frame, ops::contains)?,
+                Op::Negate => self.unary(frame, ops::negate)?,
+                Op::Not => self.unary(frame, |r| Ok(Value::Bool(!ops::is_truthy(&r))))?,
+                Op::BitNot => self.unary(frame, ops::bit_not)?,
+                Op::ToBool => {
+                    let val = self.pop();
+                    self.stack.push(Value::Bool(ops::is_truthy(&val)));
+                }
+                Op::Print => println!("{}", self.pop()),
+                Op::Iterate => {
+                    let collection = self.pop();
+                    let elements = ops::iterate(collection).map_err(|msg| frame.error(msg))?;
+                    self.stack.push(ops::array(elements));
+                }
+                Op::Next(i) => {
+                    let slot = frame.base + i as usize;
+                    let (Value::Array(elements), Value::Int(next)) =
+                        (&self.stack[slot], &self.stack[slot + 1])
+                    else {
+                        panic!("No array to iterate.");
+                    };
+                    let next = *next as usize;
+                    let element = elements.borrow().get(next).cloned();
+                    match element {
+                        Some(element) => {
+                            self.stack[slot + 1] = Value::Int(next as i64 + 1);
+                            self.stack.push(element);
+                            self.stack.push(Value::Bool(true));
+                        }
+                        None => self.stack.push(Value::Bool(false)),
+                    }
+                }
+                Op::Jump(target) => frame.ip = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !ops::is_truthy(&self.pop()) {
+                        frame.ip = target as usize;
+                    }
+                }
+                Op::Call(argc) => {
+                    if let Some(callee) =
+                        self.call(argc as usize).map_err(|msg| frame.error(&msg))?
+                    {
+                        // 木を辿る実行方式と同じ深さで止める
+                        if self.frames.len() >= self.max_depth {
+                            return Err(frame.error("Stack overflow.").into());
+                        }
+                        self.frames.push(std::mem::replace(frame, callee));
+                    }
+                }
+                Op::Closure(i) => {
+                    let proto = Rc::clone(&frame.closure.proto.chunk.functions[i as usize]);
+                    let upvalues = proto
+                        .upvalues
+                        .iter()
+                        .map(|capture| match capture {
+                            Capture::Local(i) => self.capture(frame.base + *i as usize),
+                            Capture::Upvalue(i) => Rc::clone(&frame.closure.upvalues[*i as usize]),
+                        })
+                        .collect();
+                    self.stack.push(Value::Closure(gc::alloc(Closure {
+                        proto,
+                        upvalues,
+                        globals: Rc::clone(&frame.closure.globals),
+                    })));
+                }
+                Op::Return => {
+                    let result = self.pop();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base - 1);
+                    if self.frames.len() == depth {
+                        return Ok(result);
+                    }
+                    self.stack.push(result);
+                    *frame = self.frames.pop().expect("No frame to return to.");
+                }
+                Op::Try(target) | Op::TryFinally(target) => self.handlers.push(Handler {
+                    run: self.runs,
+                    frame: self.frames.len(),
+                    height: self.stack.len(),
+                    target: target as usize,
+                    finally: matches!(op, Op::TryFinally(_)),
+                }),
+                Op::EndTry => {
+                    self.handlers.pop();
+                }
+                Op::Throw => {
+                    return Err(match self.pop() {
+                        // 捕まえた組み込みのエラーは元のエラーとして投げ直す
+                        Value::Error(error) => Exception::Error((*error).clone()),
+                        val => Exception::Throw(Box::new(frame.error(&ops::uncaught(&val))), val),
+                    });
+                }
+                Op::Rethrow => {
+                    let Value::Int(i) = self.pop() else {
+                        panic!("No pending exception to rethrow.");
+                    };
+                    self.pending.truncate(i as usize + 1);
+                    return Err(self.pending.pop().expect("No pending exception."));
+                }
+            }
+        }
+    }
+
+    fn load_module(&mut self, frame: &Frame, path: &str) -> Result<Value, Error> {
+        let at = |msg: &str| frame.error(msg);
+        let source = match self.loader.load(at, path, self.builtin_names())? {
+            Load::Cached(module) => return Ok(module),
+            Load::Source(source) => source,
+        };
+
+        let env = Environment::enclosed_by(&self.globals);
+        let script = self
+            .script(&source.statements, Rc::clone(&source.src), &env)
+            .map_err(|errors| Loader::error(errors, &source.file))?;
+        let previous_exports = std::mem::take(&mut self.exports);
+        self.loader.enter(&source);
+
+        let result = self.call_value(script, vec![], None);
+
+        self.loader.leave();
+        let exports = std::mem::replace(&mut self.exports, previous_exports);
+        result.map_err(|e| Loader::error(vec![e], &source.file))?;
+
+        let module = Value::Module(gc::alloc(Module {
+            path: source.file.clone(),
+            env,
+            exports,
+        }));
+        self.loader.cache(source, module.clone());
+        Ok(module)
+    }
+
+    fn unary(
+        &mut self,
+        frame: &Frame,
+        op: impl Fn(Value) -> Result<Value, &'static str>,
+    ) -> Result<(), Error> {
+        let right = self.pop();
+        let val = op(right).map_err(|msg| frame.error(msg))?;
+        self.stack.push(val);
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        frame: &Frame,
+        op: impl Fn(Value, Value) -> Result<Value, &'static str>,
+    ) -> Result<(), Error> {
+        let right = self.pop();
+        let left = self.pop();
+        let val = op(left, right).map_err(|msg| frame.error(msg))?;
+        self.stack.push(val);
+        Ok(())
+    }
+
+    // 初期化されていない変数は読めない
+    fn push_initialized(&mut self, frame: &Frame, val: Value) -> Result<(), Error> {
+        if val == Value::Undefined {
+            return Err(frame.error("Variable not initialized."));
+        }
+        self.stack.push(val);
+        Ok(())
+    }
+
+    fn capture(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let i = self
+            .open_upvalues
+            .partition_point(|u| matches!(*u.borrow(), Upvalue::Open(s) if s < slot));
+        if let Some(upvalue) = self.open_upvalues.get(i) {
+            if matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot) {
+                return Rc::clone(upvalue);
+            }
+        }
+        let upvalue = gc::alloc(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(i, Rc::clone(&upvalue));
+        upvalue
+    }
+
+    // from以降の位置を指すアップバリューに値を移す
+    fn close_upvalues(&mut self, from: usize) {
+        let i = self
+            .open_upvalues
+            .partition_point(|u| matches!(*u.borrow(), Upvalue::Open(s) if s < from));
+        for upvalue in self.open_upvalues.drain(i..) {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) => slot,
+                Upvalue::Closed(_) => panic!("Closed upvalue in the open list."),
+            };
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+        }
+    }
+
+    fn pop_locals(&mut self, n: usize) {
+        let height = self.stack.len() - n;
+        self.close_upvalues(height);
+        self.stack.truncate(height);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Stack underflow.")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("Stack underflow.")
+    }
+}
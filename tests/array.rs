@@ -0,0 +1,110 @@
+mod run;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use jasc::ast::Value;
+
+fn array(elements: Vec<Value>) -> Value {
+    Value::Array(Rc::new(RefCell::new(elements)))
+}
+
+#[test]
+fn literal() {
+    run::ok("[];", array(vec![]));
+    run::ok(
+        "[1, 1 + 1, true];",
+        array(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Bool(true),
+        ]),
+    );
+    run::ok(
+        "[[1], []];",
+        array(vec![array(vec![Value::Number(1.0)]), array(vec![])]),
+    );
+}
+
+#[test]
+fn index() {
+    run::ok("[1, 2, 3][0];", Value::Number(1.0));
+    run::ok("let a = [1, 2, 3]; a[1 + 1];", Value::Number(3.0));
+    run::ok("let a = [[1, 2], [3, 4]]; a[1][0];", Value::Number(3.0));
+}
+
+#[test]
+fn index_assignment() {
+    run::ok("let a = [1, 2, 3]; a[1] = 5; a;", {
+        array(vec![
+            Value::Number(1.0),
+            Value::Number(5.0),
+            Value::Number(3.0),
+        ])
+    });
+    run::ok("let a = [1, 2, 3]; a[0] = a[2] = 4;", Value::Number(4.0));
+    run::ok(
+        "let a = [[1], [2]]; a[1][0] = 3; a[1][0];",
+        Value::Number(3.0),
+    );
+}
+
+#[test]
+fn reference() {
+    run::ok(
+        "let a = [1]; let b = a; b[0] = 2; a[0];",
+        Value::Number(2.0),
+    );
+    run::ok(
+        "let a = [0]; let inc = function () { a[0] = a[0] + 1; }; inc(); inc(); a[0];",
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn call_and_index() {
+    run::ok(
+        "let f = function () { [function (a) { a + 1; }]; }; f()[0](1);",
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn cycle() {
+    run::ok("let a = [0]; a[0] = a; a == a;", Value::Bool(true));
+    run::ok(
+        "let a = [0]; a[0] = a; let b = [0]; b[0] = b; a == b;",
+        Value::Bool(true),
+    );
+    run::ok(
+        "let a = [0]; a[0] = a; let b = [1]; b[0] = [b]; a == b;",
+        Value::Bool(true),
+    );
+    run::ok("let a = [0]; a[0] = a; a == [[1]];", Value::Bool(false));
+    let a = run::eval("let a = [1, 0]; a[1] = a; a;");
+    assert_eq!(format!("{}", a), "[1, [...]]");
+    let a = run::eval("let a = [0]; let b = [a, a]; a[0] = b; b;");
+    assert_eq!(format!("{}", a), "[[[...]], [[...]]]");
+}
+
+#[test]
+fn index_error() {
+    run::err1("[1, 2][2];", 1, "[", "Index out of range.");
+    run::err1("[1, 2][-1];", 1, "[", "Index out of range.");
+    run::err1("[1, 2][1 / 2];", 1, "[", "Index must be an integer.");
+    run::err1("[1, 2][true];", 1, "[", "Index must be a number.");
+    run::err1(
+        "1[0];",
+        1,
+        "[",
+        "Only arrays and dictionaries can be indexed.",
+    );
+    run::err1("let a = [1];\na[1] = 2;", 2, "[", "Index out of range.");
+}
+
+#[test]
+fn parse_error() {
+    run::err1("[1, 2;", 1, ";", "Comma expected.");
+    run::err1("[1, 2,", 1, "end", "No closing bracket.");
+    run::err1("a[1;", 1, ";", "Right bracket expected.");
+}
@@ -5,6 +5,12 @@ fn number() {
     run::parse("123;", "(expression 123)")
 }
 
+#[test]
+fn string() {
+    run::parse(r#""abc";"#, r#"(expression "abc")"#);
+    run::parse(r#""a\n" + "b";"#, r#"(expression (+ "a\n" "b"))"#);
+}
+
 #[test]
 fn test_bool() {
     run::parse("true;", "(expression true)");
@@ -17,6 +23,40 @@ fn unary() {
     run::parse("-true;", "(expression (- true))");
 }
 
+#[test]
+fn nested_unary() {
+    run::parse("- -1;", "(expression (- (- 1)))");
+    run::parse("-f(x);", "(expression (- (call (var f) ((var x)))))");
+    run::parse("!a[0];", "(expression (! (index (var a) 0)))");
+    run::parse("~!-x;", "(expression (~ (! (- (var x)))))");
+}
+
+#[test]
+fn precedence() {
+    // 弱いものから順に
+    let table = [
+        ("a || b && c", "(|| (var a) (&& (var b) (var c)))"),
+        ("a && b == c", "(&& (var a) (== (var b) (var c)))"),
+        ("a == b < c", "(== (var a) (< (var b) (var c)))"),
+        ("a < b | c", "(< (var a) (| (var b) (var c)))"),
+        ("a | b ^ c", "(| (var a) (^ (var b) (var c)))"),
+        ("a ^ b & c", "(^ (var a) (& (var b) (var c)))"),
+        ("a & b << c", "(& (var a) (<< (var b) (var c)))"),
+        ("a >> b + c", "(>> (var a) (+ (var b) (var c)))"),
+        ("a - b % c", "(- (var a) (% (var b) (var c)))"),
+        ("a ~/ -b", "(~/ (var a) (- (var b)))"),
+        ("-a ** b", "(- (** (var a) (var b)))"),
+        ("a ** -b", "(** (var a) (- (var b)))"),
+        ("a ** b ** c", "(** (var a) (** (var b) (var c)))"),
+        ("!a ** b()", "(! (** (var a) (call (var b) ())))"),
+        ("a << b >> c", "(>> (<< (var a) (var b)) (var c))"),
+        ("a & b & c", "(& (& (var a) (var b)) (var c))"),
+    ];
+    for (src, expected) in table {
+        run::parse(&format!("{};", src), &format!("(expression {})", expected));
+    }
+}
+
 #[test]
 fn term() {
     run::parse("12 * -34 / 56;", "(expression (/ (* 12 (- 34)) 56))");
@@ -67,6 +107,20 @@ fn and() {
     run::parse("true && -false;", "(expression (&& true (- false)))");
 }
 
+#[test]
+fn equality() {
+    run::parse("1 == 2;", "(expression (== 1 2))");
+    run::parse("1 != 2 == true;", "(expression (== (!= 1 2) true))");
+    run::parse("1 == 2 && 3 != 4;", "(expression (&& (== 1 2) (!= 3 4)))");
+}
+
+#[test]
+fn comparison() {
+    run::parse("1 < 2;", "(expression (< 1 2))");
+    run::parse("1 + 2 <= 3 * 4;", "(expression (<= (+ 1 2) (* 3 4)))");
+    run::parse("1 > 2 == 3 >= 4;", "(expression (== (> 1 2) (>= 3 4)))");
+}
+
 #[test]
 fn let_() {
     run::parse("let a = 1;", "(let a 1)");
@@ -84,6 +138,102 @@ fn assignment() {
     run::parse("a = true;", "(expression (assignment a true))");
 }
 
+#[test]
+fn compound_assignment() {
+    run::parse("a += 1;", "(expression (+= (var a) 1))");
+    run::parse(
+        "a[i] -= b *= 2;",
+        "(expression (-= (index (var a) (var i)) (*= (var b) 2)))",
+    );
+    run::parse("m.x /= 2;", r#"(expression (/= (index (var m) "x") 2))"#);
+    run::parse(
+        "a %= b ? 1 : 2;",
+        "(expression (%= (var a) (? (var b) 1 2)))",
+    );
+}
+
+#[test]
+fn increment() {
+    run::parse("i++;", "(expression (post++ (var i)))");
+    run::parse("--a[0];", "(expression (pre-- (index (var a) 0)))");
+    run::parse("-i++;", "(expression (- (post++ (var i))))");
+    run::parse("++i ** 2;", "(expression (** (pre++ (var i)) 2))");
+    run::parse("- -i;", "(expression (- (- (var i))))");
+}
+
+#[test]
+fn invalid_assignment_target() {
+    run::err1("(a + b) += 1;", 1, "+=", "Invalid assignment target.");
+    run::err1("a + b = 1;", 1, "=", "Invalid assignment target.");
+    run::err1("f() = 1;", 1, "=", "Invalid assignment target.");
+    run::err1("1++;", 1, "++", "Invalid assignment target.");
+    run::err1("--f();", 1, "--", "Invalid assignment target.");
+}
+
+#[test]
+fn doc_comment() {
+    run::parse(
+        "/// The answer.\nlet a = 42;",
+        r#"(let a 42 (doc "The answer."))"#,
+    );
+    run::parse(
+        "/// Exported.\n// note\nexport let f = 1;",
+        r#"(export (let f 1 (doc "Exported.")))"#,
+    );
+    run::parse(
+        "/// Dropped.\n1;\nlet b;",
+        "(expression 1) (let b undefined)",
+    );
+}
+
+#[test]
+fn array() {
+    run::parse("[];", "(expression (array))");
+    run::parse("[1, 2 + 3];", "(expression (array 1 (+ 2 3)))");
+    run::parse("a[0];", "(expression (index (var a) 0))");
+    run::parse("a[0][1];", "(expression (index (index (var a) 0) 1))");
+    run::parse(
+        "a[0] = b[1] = 2;",
+        "(expression (assignment (index (var a) 0) (assignment (index (var b) 1) 2)))",
+    );
+}
+
+#[test]
+fn dict() {
+    run::parse("let m = {};", "(let m (dict))");
+    run::parse(
+        r#"let m = { "a": 1, b: 2 + 3 };"#,
+        r#"(let m (dict ("a" 1) ("b" (+ 2 3))))"#,
+    );
+    run::parse(r#"{ a: 1 };"#, r#"(expression (dict ("a" 1)))"#);
+    run::parse("{ a; }", "(block (expression (var a)))");
+    run::parse("m.a.b;", r#"(expression (index (index (var m) "a") "b"))"#);
+    run::parse(
+        "m.a = 1;",
+        r#"(expression (assignment (index (var m) "a") 1))"#,
+    );
+    run::parse(
+        r#""a" in m == true;"#,
+        r#"(expression (== (in "a" (var m)) true))"#,
+    );
+}
+
+#[test]
+fn call() {
+    run::parse("f();", "(expression (call (var f) ()))");
+    run::parse("f(1, 2);", "(expression (call (var f) (1 2)))");
+    run::parse(
+        "f()[0](1);",
+        "(expression (call (index (call (var f) ()) 0) (1)))",
+    );
+}
+
+#[test]
+fn module() {
+    run::parse(r#"import "a/b.jsc" as b;"#, r#"(import "a/b.jsc" b)"#);
+    run::parse("export let a = 1;", "(export (let a 1))");
+}
+
 #[test]
 fn block() {
     run::parse("{}", "(block )");
@@ -130,8 +280,34 @@ fn test_while() {
     );
 }
 
+#[test]
+fn test_for() {
+    run::parse(
+        "for (let i = 0; i < 3; i = i + 1) print(i);",
+        "(for (let i 0) (< (var i) 3) (assignment i (+ (var i) 1)) (print (group (var i))))",
+    );
+    run::parse("for (;;) break;", "(for () () () (break))");
+    run::parse(
+        "for (i = 0; ; ) {}",
+        "(for (expression (assignment i 0)) () () (block ))",
+    );
+    run::parse(
+        "for (x in [1, 2]) print(x);",
+        "(for-in x (array 1 2) (print (group (var x))))",
+    );
+}
+
 #[test]
 fn test_break() {
     run::parse("while (1) break;", "(while 1 (break))");
     run::parse("while (1) { break; }", "(while 1 (block (break)))");
 }
+
+#[test]
+fn test_continue() {
+    run::parse("while (1) continue;", "(while 1 (continue))");
+    run::parse(
+        "while (1) { if (1) continue; break; }",
+        "(while 1 (block (if 1 (continue)) (break)))",
+    );
+}
@@ -0,0 +1,38 @@
+mod run;
+
+use jasc::ast::Value;
+
+#[test]
+fn and_or_xor() {
+    run::ok("12 & 10;", Value::Int(8));
+    run::ok("12 | 10;", Value::Int(14));
+    run::ok("12 ^ 10;", Value::Int(6));
+    run::ok("-1 & 255;", Value::Int(255));
+    // 整数値の小数も使える
+    run::ok("6.0 & 3;", Value::Int(2));
+}
+
+#[test]
+fn shift() {
+    run::ok("1 << 10;", Value::Int(1024));
+    run::ok("1024 >> 3;", Value::Int(128));
+    run::ok("-16 >> 2;", Value::Int(-4));
+    run::ok("1 << 63;", Value::Int(i64::MIN));
+    run::err1("1 << 64;", 1, "<<", "Shift amount out of range.");
+    run::err1("1 >> -1;", 1, ">>", "Shift amount out of range.");
+}
+
+#[test]
+fn precedence() {
+    run::ok("5 & 1 == 1;", Value::Bool(true));
+    run::ok("1 | 2 ^ 3 & 4;", Value::Int(3));
+    run::ok("1 << 2 + 1;", Value::Int(8));
+}
+
+#[test]
+fn not_integer() {
+    run::err1("1.5 & 1;", 1, "&", "Operands must be integers.");
+    run::err1("1 | \"a\";", 1, "|", "Operands must be integers.");
+    run::err1("true ^ 1;", 1, "^", "Operands must be integers.");
+    run::err1("1 << 0.5;", 1, "<<", "Operands must be integers.");
+}
@@ -0,0 +1,34 @@
+mod run;
+
+use jasc::ast::Value;
+
+#[test]
+fn len() {
+    run::ok("len([1, 2, 3]);", Value::Number(3.0));
+    run::ok("len({ a: 1 });", Value::Number(1.0));
+    run::ok(r#"len("あいう");"#, Value::Number(3.0));
+    run::err1(
+        "len(1);",
+        1,
+        "(",
+        "Argument must be an array, a dictionary or a string.",
+    );
+    run::err1("len();", 1, "(", "Number of the arguments does not match.");
+}
+
+#[test]
+fn sqrt() {
+    run::ok("sqrt(16);", Value::Number(4.0));
+    run::err1("sqrt(true);", 1, "(", "Argument must be a number.");
+}
+
+#[test]
+fn clock() {
+    run::ok("clock() > 0;", Value::Bool(true));
+}
+
+#[test]
+fn shadowing() {
+    run::ok("let len = 1; len;", Value::Number(1.0));
+    run::ok("let f = len; f([1]);", Value::Number(1.0));
+}
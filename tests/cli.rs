@@ -0,0 +1,159 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn jasc(args: &[&str], input: &str) -> (Option<i32>, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jasc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start jasc");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn stdin() {
+    assert_eq!(
+        jasc(&[], "print 1 + 2;"),
+        (Some(0), "3\n".to_string(), "".to_string())
+    );
+    assert_eq!(
+        jasc(&["-", "a"], "print args;"),
+        (Some(0), "[\"a\"]\n".to_string(), "".to_string())
+    );
+}
+
+#[test]
+fn script_file() {
+    assert_eq!(
+        jasc(&["tests/scripts/args.jsc", "a", "-e", "b c"], ""),
+        (
+            Some(0),
+            "3\n[\"a\", \"-e\", \"b c\"]\n".to_string(),
+            "".to_string()
+        )
+    );
+    assert_eq!(
+        jasc(&["tests/scripts/args.jsc"], ""),
+        (Some(0), "0\n[]\n".to_string(), "".to_string())
+    );
+}
+
+#[test]
+fn import_relative_to_script() {
+    assert_eq!(
+        jasc(&["tests/scripts/import.jsc"], ""),
+        (Some(0), "10\n".to_string(), "".to_string())
+    );
+}
+
+#[test]
+fn inline() {
+    assert_eq!(
+        jasc(&["-e", "print args[0] + args[1];", "x", "y"], ""),
+        (Some(0), "xy\n".to_string(), "".to_string())
+    );
+    assert_eq!(
+        jasc(&["-e", "1 + 2;"], ""),
+        (Some(0), "3\n".to_string(), "".to_string())
+    );
+}
+
+#[test]
+fn vm_backend() {
+    assert_eq!(
+        jasc(&["--vm", "tests/scripts/import.jsc"], ""),
+        (Some(0), "10\n".to_string(), "".to_string())
+    );
+    assert_eq!(
+        jasc(&["--vm", "tests/scripts/runtime_error.jsc"], ""),
+        (
+            Some(70),
+            "1\n".to_string(),
+            "[line 2:9] Error at '-': Operands must be two numbers.\n".to_string()
+        )
+    );
+}
+
+#[test]
+fn syntax_error() {
+    assert_eq!(
+        jasc(&["tests/scripts/syntax_error.jsc"], ""),
+        (
+            Some(65),
+            "".to_string(),
+            "[line 3:1] Error at 'end': Semicolon expected.\n".to_string()
+        )
+    );
+    assert_eq!(jasc(&["-e", "@"], "").0, Some(65));
+    assert_eq!(
+        jasc(&["-e", "print 1; print nothing;"], ""),
+        (
+            Some(65),
+            "".to_string(),
+            "[line 1:16] Error at 'nothing': Variable not defined.\n".to_string()
+        )
+    );
+}
+
+#[test]
+fn runtime_error() {
+    assert_eq!(
+        jasc(&["tests/scripts/runtime_error.jsc"], ""),
+        (
+            Some(70),
+            "1\n".to_string(),
+            "[line 2:9] Error at '-': Operands must be two numbers.\n".to_string()
+        )
+    );
+    assert_eq!(jasc(&[], "1(2);").0, Some(70));
+}
+
+#[test]
+fn rich_errors() {
+    assert_eq!(
+        jasc(&["--errors", "rich", "tests/scripts/runtime_error.jsc"], ""),
+        (
+            Some(70),
+            "1\n".to_string(),
+            "error: Operands must be two numbers.\n --> line 2:9\n  |\n2 | print 1 - \"a\";\n  |         ^\n"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        jasc(&["--errors", "plain", "-e", "@"], ""),
+        (
+            Some(65),
+            "".to_string(),
+            "[line 1:1] Error at '@': Unexpected character.\n".to_string()
+        )
+    );
+}
+
+#[test]
+fn usage_error() {
+    assert_eq!(jasc(&["-e"], "").0, Some(64));
+    assert_eq!(jasc(&["--unknown"], "").0, Some(64));
+    assert_eq!(jasc(&["--errors", "fancy", "-e", "1;"], "").0, Some(64));
+    assert_eq!(jasc(&["--repl", "tests/scripts/args.jsc"], "").0, Some(64));
+}
+
+#[test]
+fn missing_file() {
+    let (code, out, err) = jasc(&["tests/scripts/nothing.jsc"], "");
+    assert_eq!(code, Some(66));
+    assert_eq!(out, "");
+    assert!(err.starts_with("Error: failed to read 'tests/scripts/nothing.jsc'"));
+}
@@ -0,0 +1,63 @@
+mod run;
+
+use jasc::ast::Value;
+
+#[test]
+fn less() {
+    run::ok("1 < 2;", Value::Bool(true));
+    run::ok("2 < 2;", Value::Bool(false));
+    run::ok("1 <= 2;", Value::Bool(true));
+    run::ok("2 <= 2;", Value::Bool(true));
+    run::ok("3 <= 2;", Value::Bool(false));
+}
+
+#[test]
+fn greater() {
+    run::ok("2 > 1;", Value::Bool(true));
+    run::ok("2 > 2;", Value::Bool(false));
+    run::ok("2 >= 1;", Value::Bool(true));
+    run::ok("2 >= 2;", Value::Bool(true));
+    run::ok("2 >= 3;", Value::Bool(false));
+}
+
+#[test]
+fn equal() {
+    run::ok("1 == 1;", Value::Bool(true));
+    run::ok("1 == 2;", Value::Bool(false));
+    run::ok("1 != 2;", Value::Bool(true));
+    run::ok("true == true;", Value::Bool(true));
+    run::ok("true != false;", Value::Bool(true));
+    run::ok("1 == true;", Value::Bool(false));
+    run::ok("1 + 2 == 6 / 2;", Value::Bool(true));
+}
+
+#[test]
+fn equal_function() {
+    run::ok("let f = function () {}; f == f;", Value::Bool(true));
+    run::ok(
+        "let f = function () {}; let g = f; f == g;",
+        Value::Bool(true),
+    );
+    run::ok("function () {} == function () {};", Value::Bool(false));
+}
+
+#[test]
+fn type_mismatch() {
+    run::err1(
+        "1 < true;",
+        1,
+        "<",
+        "Operands must be two numbers or two strings.",
+    );
+    run::err1(
+        "false >= 1;",
+        1,
+        ">=",
+        "Operands must be two numbers or two strings.",
+    );
+}
+
+#[test]
+fn bang_only() {
+    run::err1("1 ! 2;", 1, "!", "Semicolon expected.");
+}
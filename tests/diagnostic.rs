@@ -0,0 +1,124 @@
+use jasc::error::Error;
+
+fn render(src: &str) -> String {
+    match jasc::run(src) {
+        Err(errors) => errors[0].render(src, false),
+        result => panic!("Failed - result: {:?}", result),
+    }
+}
+
+#[test]
+fn underline_token() {
+    assert_eq!(
+        render("let a = 1;\nprint a + b;"),
+        "\
+error: Variable not defined.
+ --> line 2:11
+  |
+2 | print a + b;
+  |           ^
+"
+    );
+    assert_eq!(
+        render("let abc = 1;\nabc(2);"),
+        "\
+error: Callee is not a function.
+ --> line 2:4
+  |
+2 | abc(2);
+  |    ^
+"
+    );
+}
+
+#[test]
+fn underline_whole_lexeme() {
+    assert_eq!(
+        render("print 1 + undefined_variable;"),
+        "\
+error: Variable not defined.
+ --> line 1:11
+  |
+1 | print 1 + undefined_variable;
+  |           ^^^^^^^^^^^^^^^^^^
+"
+    );
+}
+
+#[test]
+fn keep_tabs() {
+    assert_eq!(
+        render("\tprint\tx;"),
+        "\
+error: Variable not defined.
+ --> line 1:8
+  |
+1 | \tprint\tx;
+  | \t     \t^
+"
+    );
+}
+
+#[test]
+fn note_declared_here() {
+    assert_eq!(
+        render("let a = 1;\n{\n}\nlet a = 2;"),
+        "\
+error: Variable already defined.
+ --> line 4:5
+  |
+4 | let a = 2;
+  |     ^
+  |
+1 | let a = 1;
+  |     - Variable declared here.
+"
+    );
+}
+
+#[test]
+fn end_of_input() {
+    assert_eq!(
+        render("print (1"),
+        "\
+error: Right paren expected
+ --> line 1:9
+  |
+1 | print (1
+  |         ^
+"
+    );
+}
+
+#[test]
+fn without_span() {
+    let error = Error::new(3, "", "Something wrong.")
+        .in_file("lib.jsc")
+        .with_note(1, "Imported here.", None);
+    assert_eq!(
+        error.render("", false),
+        "error: Something wrong.\n --> lib.jsc:3\n  = note: Imported here.\n"
+    );
+}
+
+#[test]
+fn color() {
+    let rendered = jasc::run("print x;").unwrap_err()[0].render("print x;", true);
+    assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m: "));
+    assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+}
+
+#[test]
+fn stack_trace() {
+    assert_eq!(
+        render("let f = function() {\n  1 - true;\n};\nf();"),
+        "\
+error: Operands must be two numbers.
+ --> line 2:5
+  |
+2 |   1 - true;
+  |     ^
+  = in f, called at line 4
+"
+    );
+}
@@ -0,0 +1,110 @@
+mod run;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use jasc::ast::Value;
+use jasc::dict::Dict;
+
+fn dict(entries: Vec<(&str, Value)>) -> Value {
+    let dict = entries
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect::<Dict>();
+    Value::Dict(Rc::new(RefCell::new(dict)))
+}
+
+#[test]
+fn literal() {
+    run::ok(
+        r#"let m = { "a": 1, b: 2 }; m["a"] + m["b"];"#,
+        Value::Number(3.0),
+    );
+    run::ok("let m = {}; m;", dict(vec![]));
+    run::ok(r#"{ "a": 1, a: 2 }["a"];"#, Value::Number(2.0));
+}
+
+#[test]
+fn dot() {
+    run::ok("let m = { a: { b: 3 } }; m.a.b;", Value::Number(3.0));
+    run::ok(
+        "let m = { f: function (x) { x * 2; } }; m.f(4);",
+        Value::Number(8.0),
+    );
+}
+
+#[test]
+fn insertion() {
+    run::ok(
+        r#"let m = {}; m["a"] = 1; m.b = 2; m.a + m["b"];"#,
+        Value::Number(3.0),
+    );
+    run::ok("let m = { a: 1 }; m.a = 5; m.a;", Value::Number(5.0));
+    run::ok("let m = {}; let n = m; n.a = 1; m.a;", Value::Number(1.0));
+}
+
+#[test]
+fn membership() {
+    run::ok(r#""a" in { a: 1 };"#, Value::Bool(true));
+    run::ok(r#""b" in { a: 1 };"#, Value::Bool(false));
+    run::ok("2 in [1, 2, 3];", Value::Bool(true));
+    run::ok("4 in [1, 2, 3];", Value::Bool(false));
+}
+
+#[test]
+fn equality() {
+    run::ok("({ a: 1, b: 2 } == { b: 2, a: 1 });", Value::Bool(true));
+    run::ok("({ a: 1, b: 2 } == { a: 1, b: 3 });", Value::Bool(false));
+    run::ok("({ a: 1, b: 2 } == { a: 1, c: 2 });", Value::Bool(false));
+    run::ok("({ a: 1 } == { a: 1, b: 2 });", Value::Bool(false));
+    run::ok("({ a: 1, b: 2 } != { b: 2, a: 1 });", Value::Bool(false));
+    run::ok("({} == {});", Value::Bool(true));
+    run::ok(
+        "let d = { a: 1, b: 2 }; d;",
+        dict(vec![("b", Value::Number(2.0)), ("a", Value::Number(1.0))]),
+    );
+}
+
+#[test]
+fn cycle() {
+    run::ok("let d = {}; d.d = d; d == d;", Value::Bool(true));
+    run::ok(
+        "let d = {}; d.d = d; let e = {}; e.d = e; d == e;",
+        Value::Bool(true),
+    );
+    run::ok("let d = {}; d.d = d; d == { d: {} };", Value::Bool(false));
+    let d = run::eval("let d = { a: 1 }; d.d = d; d;");
+    assert_eq!(format!("{}", d), r#"{"a": 1, "d": {...}}"#);
+    let d = run::eval("let d = {}; d.a = [d]; d;");
+    assert_eq!(format!("{}", d), r#"{"a": [{...}]}"#);
+}
+
+#[test]
+fn block_or_dict() {
+    run::ok("{ a: 1 };", dict(vec![("a", Value::Number(1.0))]));
+    run::ok("{ 1; }", Value::Number(1.0));
+    run::ok("let a = 1; { a ? 2 : 3; }", Value::Number(2.0));
+}
+
+#[test]
+fn key_error() {
+    run::err1("let m = { a: 1 };\nm.b;", 2, ".", "Key not found.");
+    run::err1(r#"{ a: 1 }["b"];"#, 1, "[", "Key not found.");
+    run::err1("{ a: 1 }[1];", 1, "[", "Key must be a string.");
+    run::err1("1 in { a: 1 };", 1, "in", "Key must be a string.");
+    run::err1(
+        r#""a" in 1;"#,
+        1,
+        "in",
+        "Right operand must be an array or a dictionary.",
+    );
+}
+
+#[test]
+fn parse_error() {
+    run::err1("let m = { 1: 2 };", 1, "1", "Key expected.");
+    run::err1("let m = { a 2 };", 1, "2", "Colon expected.");
+    run::err1("let m = { a: 1 b: 2 };", 1, "b", "Comma expected.");
+    run::err1("let m = { a: 1,", 1, "end", "No closing brace.");
+    run::err1("m.1;", 1, "1", "Property name expected.");
+}
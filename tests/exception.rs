@@ -0,0 +1,189 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::error::Error;
+
+#[test]
+fn catch_thrown_value() {
+    run::ok("try { throw 1; } catch (e) { e + 1; }", Value::Number(2.0));
+    run::ok(
+        r#"let r = 0; try { throw {"code": 3}; } catch (e) { r = e.code; } r;"#,
+        Value::Number(3.0),
+    );
+    run::ok("try { 1; } catch (e) { 2; }", Value::Number(1.0));
+}
+
+#[test]
+fn catch_runtime_error() {
+    run::ok(
+        "try { 1(); } catch (e) { e.message; }",
+        Value::String("Callee is not a function.".to_string()),
+    );
+    run::ok(
+        "try {\n\n [1][3]; } catch (e) { e.line; }",
+        Value::Number(3.0),
+    );
+    run::err1(
+        "try { 1(); } catch (e) { e.file; }",
+        1,
+        ".",
+        "Key not found.",
+    );
+}
+
+#[test]
+fn catch_across_calls() {
+    run::ok(
+        r#"let deep = function(n) { if (n == 0) { throw "bottom"; } deep(n - 1) + 1; };
+        let r = 0;
+        try { r = deep(50); } catch (e) { r = e; }
+        r;"#,
+        Value::String("bottom".to_string()),
+    );
+    // 捕まえた後もスタックの状態が正しい
+    run::ok(
+        "let f = function(x) { try { x(); } catch (e) { x; } };
+        let a = 1;
+        let b = f(2);
+        a + b + f(3);",
+        Value::Number(6.0),
+    );
+}
+
+#[test]
+fn finally() {
+    run::ok(
+        r#"let log = "";
+        try { log = log + "t"; } finally { log = log + "f"; }
+        try { throw 1; } catch (e) { log = log + "c"; } finally { log = log + "f"; }
+        log;"#,
+        Value::String("tfcf".to_string()),
+    );
+    // finally節の値は文の値にならない
+    run::ok("try { 1; } finally { 2; }", Value::Number(1.0));
+}
+
+#[test]
+fn finally_on_jump() {
+    run::ok(
+        r#"let log = "";
+        let f = function() { try { return "r"; } finally { log = log + "f"; } };
+        let r = f();
+        log = log + r;
+        let i = 0;
+        while (i < 3) {
+            i = i + 1;
+            try { if (i == 1) { continue; } if (i == 2) { break; } } finally { log = log + i; }
+        }
+        log;"#,
+        Value::String("fr12".to_string()),
+    );
+    // finally節のreturnは例外より優先する
+    run::ok(
+        "let f = function() { try { throw 1; } finally { return 2; } }; f();",
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn rethrow() {
+    run::ok(
+        r#"let r = "";
+        try { try { throw "in"; } finally { r = "f"; } } catch (e) { r = r + e; }
+        r;"#,
+        Value::String("fin".to_string()),
+    );
+    run::ok(
+        r#"let r = "";
+        try { try { throw "a"; } catch (e) { throw e + "b"; } finally { r = "f"; } }
+        catch (e) { r = r + e; }
+        r;"#,
+        Value::String("fab".to_string()),
+    );
+    // 組み込みのエラーは元の位置で報告される
+    run::err1(
+        "try { 1(); } catch (e) { throw e; }",
+        1,
+        "(",
+        "Callee is not a function.",
+    );
+    run::err1(
+        "let x = 0; try { 1(); } finally { x = 1; }",
+        1,
+        "(",
+        "Callee is not a function.",
+    );
+}
+
+#[test]
+fn uncaught() {
+    run::err1("throw \"oops\";", 1, "throw", "Uncaught exception: oops");
+    run::err1(
+        "let f = function() { throw [1, 2]; };\nf();",
+        1,
+        "throw",
+        "Uncaught exception: [1, 2]",
+    );
+    run::err1(
+        "try { throw 1; } finally { }",
+        1,
+        "throw",
+        "Uncaught exception: 1",
+    );
+}
+
+#[test]
+fn catch_variable() {
+    run::ok(
+        "let f = 0; try { throw 5; } catch (e) { f = function() { e; }; } f();",
+        Value::Number(5.0),
+    );
+    run::err1(
+        "try { throw 1; } catch (e) { } e;",
+        1,
+        "e",
+        "Variable not defined.",
+    );
+}
+
+#[test]
+fn module_error() {
+    run::ok(
+        r#"let r = 0;
+        try { import "tests/modules/runtime_error.jsc" as m; } catch (e) { r = e.message; }
+        r;"#,
+        Value::String("Variable not defined.".to_string()),
+    );
+    // モジュールの中のthrowは、importを囲むtryで捕まえて続きを実行する
+    run::ok(
+        r#"let r = "";
+        try { import "tests/modules/throw.jsc" as m; } catch (e) { r = e.message; }
+        r = r + ", after";
+        r;"#,
+        Value::String("Uncaught exception: boom, after".to_string()),
+    );
+    run::ok(
+        r#"let f = function() {
+            try { import "tests/modules/throw.jsc" as m; } finally { return "finally"; }
+        };
+        f();"#,
+        Value::String("finally".to_string()),
+    );
+}
+
+#[test]
+fn parse_error() {
+    run::err1("try 1;", 1, "1", "Left brace expected.");
+    run::err1("try { }", 1, "end", "Catch or finally expected.");
+    run::err1("try { } catch e { }", 1, "e", "Left paren expected.");
+    run::err1("try { } catch (1) { }", 1, "1", "Identifier expected.");
+    run::err(
+        "throw;",
+        vec![Error::new(1, ";", "Expression expected, found `;`")],
+    );
+    run::parse(
+        "try { 1; } catch (e) { 2; } finally { 3; }",
+        "(try (block (expression 1)) (catch e (block (expression 2))) (finally (block (expression 3))))",
+    );
+    run::parse("throw 1;", "(throw 1)");
+}
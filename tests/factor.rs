@@ -47,3 +47,14 @@ fn type_mismatch() {
     run::err1("1 / false;", 1, "/", "Operands must be two numbers.");
     run::err1("true / 1;", 1, "/", "Operands must be two numbers.");
 }
+
+#[test]
+fn power() {
+    run::ok("2 ** 10;", Value::Int(1024));
+    run::ok("2 ** 3 ** 2;", Value::Int(512));
+    run::ok("-2 ** 2;", Value::Int(-4));
+    run::ok("2 ** -1;", Value::Number(0.5));
+    run::ok("4 ** 0.5;", Value::Number(2.0));
+    run::err1("2 ** 63;", 1, "**", "Integer overflow.");
+    run::err1("2 ** true;", 1, "**", "Operands must be two numbers.");
+}
@@ -0,0 +1,110 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::gc;
+use jasc::session::Session;
+use jasc::Backend;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalk, Backend::Vm];
+
+// テストはそれぞれ別のスレッドで動くので、数はこのテストで作ったものだけになる
+#[test]
+fn recursive_function_freed() {
+    for backend in BACKENDS {
+        {
+            let mut session = Session::with_backend(backend);
+            let src = "let f = function(n) { if (n < 1) { return 0; } f(n - 1); }; f(10);";
+            assert_eq!(session.eval(src), Ok(Value::Int(0)));
+        }
+        assert!(gc::live().total() > 0, "{:?}", backend);
+        assert!(gc::collect() > 0);
+        assert_eq!(gc::live(), gc::Counts::default(), "{:?}", backend);
+    }
+}
+
+#[test]
+fn self_referencing_containers_freed() {
+    for backend in BACKENDS {
+        {
+            let mut session = Session::with_backend(backend);
+            let src = r#"let a = [0]; a[0] = a; let d = {"x": 0}; d["x"] = [d]; 1;"#;
+            assert_eq!(session.eval(src), Ok(Value::Int(1)));
+        }
+        gc::collect();
+        assert_eq!(gc::live(), gc::Counts::default(), "{:?}", backend);
+    }
+}
+
+// 長く動くセッションでも、ループの中で作った循環は回収される
+#[test]
+fn cycles_in_loop_collected() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        let src = "let i = 0;
+            while (i < 5000) {
+                let g = function() { g; };
+                let a = [g];
+                a[0] = a;
+                i = i + 1;
+            }
+            i;";
+        assert_eq!(session.eval(src), Ok(Value::Int(5000)));
+        assert!(gc::live().total() < 5000, "{:?}: {:?}", backend, gc::live());
+        gc::collect();
+        assert!(gc::live().total() < 10, "{:?}: {:?}", backend, gc::live());
+    }
+}
+
+// 回収が途中で走っても、使われているものは壊れない
+#[test]
+fn reachable_objects_survive() {
+    run::ok(
+        "let make = function(n) {
+            let self = function() { if (n > 0) { n = n - 1; self(); } else { n; } };
+            self;
+        };
+        let i = 0;
+        let total = 0;
+        while (i < 3000) {
+            let f = make(2);
+            total = total + f() + len([f]);
+            i = i + 1;
+        }
+        total;",
+        Value::Int(3000),
+    );
+}
+
+#[test]
+fn host_reference_survives() {
+    for backend in BACKENDS {
+        let val = {
+            let mut session = Session::with_backend(backend);
+            session.eval("let a = [0, 1]; a[0] = a; a;").unwrap()
+        };
+        gc::collect();
+        match &val {
+            Value::Array(elements) => assert_eq!(elements.borrow()[1], Value::Int(1)),
+            _ => panic!("Array expected: {:?}", val),
+        }
+        drop(val);
+        gc::collect();
+        assert_eq!(gc::live(), gc::Counts::default(), "{:?}", backend);
+    }
+}
+
+// ホストアプリケーションから渡した配列も、スクリプトが循環させれば回収の対象になる
+#[test]
+fn host_array_collected() {
+    for backend in BACKENDS {
+        {
+            let mut session = Session::with_backend(backend);
+            session.set("args", Value::array(vec![Value::Null]));
+            let src = "let f = function() { args; }; args[0] = f; len(args);";
+            assert_eq!(session.eval(src), Ok(Value::Int(1)));
+        }
+        assert_eq!(gc::live().arrays, 1, "{:?}", backend);
+        gc::collect();
+        assert_eq!(gc::live(), gc::Counts::default(), "{:?}", backend);
+    }
+}
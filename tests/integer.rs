@@ -0,0 +1,99 @@
+mod run;
+
+use jasc::ast::Value;
+
+fn print(src: &str) -> String {
+    run::eval(src).to_string()
+}
+
+#[test]
+fn int_and_float() {
+    assert_eq!(print("1 + 2;"), "3");
+    assert_eq!(print("1 + 2.0;"), "3.0");
+    assert_eq!(print("2.5 * 2;"), "5.0");
+    assert_eq!(print("-(3);"), "-3");
+    assert_eq!(print("1e3;"), "1000.0");
+    assert_eq!(print("[1, 1.0];"), "[1, 1.0]");
+    assert_eq!(print("\"n=\" + 2 + \" \" + 0.5;"), "n=2 0.5");
+}
+
+#[test]
+fn exact_int() {
+    // 2^53を超えても整数のまま
+    run::ok("9007199254740993 + 2;", Value::Int(9007199254740995));
+    run::ok("9223372036854775807;", Value::Int(i64::MAX));
+    run::ok("-9223372036854775807 - 1;", Value::Int(i64::MIN));
+}
+
+#[test]
+fn divide() {
+    assert_eq!(print("7 / 2;"), "3.5");
+    assert_eq!(print("6 / 3;"), "2.0");
+    assert_eq!(print("1 / 0;"), "inf");
+}
+
+#[test]
+fn floor_divide() {
+    run::ok("7 ~/ 2;", Value::Int(3));
+    run::ok("-7 ~/ 2;", Value::Int(-4));
+    run::ok("7 ~/ -2;", Value::Int(-4));
+    run::ok("-7 ~/ -2;", Value::Int(3));
+    assert_eq!(print("7.5 ~/ 2;"), "3.0");
+    // `//`は割り算でなく行コメントになる
+    run::ok("7 ~/ 2; // 2;", Value::Int(3));
+    run::ok("let a = 7 // 2;\n; a;", Value::Int(7));
+    run::err1("1 ~/ 0;", 1, "~/", "Division by zero.");
+}
+
+#[test]
+fn modulo() {
+    run::ok("7 % 3;", Value::Int(1));
+    run::ok("-7 % 3;", Value::Int(2));
+    run::ok("7 % -3;", Value::Int(-2));
+    run::ok("(-9223372036854775807 - 1) % -1;", Value::Int(0));
+    assert_eq!(print("5.5 % 2;"), "1.5");
+    run::err1("1 % 0;", 1, "%", "Division by zero.");
+    run::err1("\"a\" % 2;", 1, "%", "Operands must be two numbers.");
+}
+
+#[test]
+fn overflow() {
+    run::err1("9223372036854775807 + 1;", 1, "+", "Integer overflow.");
+    run::err1("-9223372036854775807 - 2;", 1, "-", "Integer overflow.");
+    run::err1("4294967296 * 4294967296;", 1, "*", "Integer overflow.");
+    run::err1(
+        "(-9223372036854775807 - 1) ~/ -1;",
+        1,
+        "~/",
+        "Integer overflow.",
+    );
+    run::err1(
+        "let m = -9223372036854775807 - 1; -m;",
+        1,
+        "-",
+        "Integer overflow.",
+    );
+    // 小数にすれば溢れない
+    assert_eq!(print("9223372036854775807 + 1.0;"), "9223372036854775808.0");
+}
+
+#[test]
+fn compare() {
+    run::ok("1 == 1.0;", Value::Bool(true));
+    run::ok("1 != 1.5;", Value::Bool(true));
+    run::ok("[1, 2] == [1.0, 2.0];", Value::Bool(true));
+    run::ok("2.0 in [1, 2];", Value::Bool(true));
+    run::ok("1 < 1.5;", Value::Bool(true));
+    run::ok("9007199254740993 > 9007199254740992;", Value::Bool(true));
+    run::ok(
+        "9007199254740993 == 9007199254740992.0;",
+        Value::Bool(false),
+    );
+}
+
+#[test]
+fn index() {
+    run::ok("[1, 2, 3][1];", Value::Int(2));
+    run::ok("[1, 2, 3][1.0];", Value::Int(2));
+    run::ok("len(\"abc\");", Value::Int(3));
+}
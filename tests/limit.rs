@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use jasc::ast::Value;
+use jasc::error::ErrorKind;
+use jasc::session::Session;
+use jasc::Backend;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalk, Backend::Vm];
+
+fn kind(session: &mut Session, src: &str) -> ErrorKind {
+    match session.eval(src) {
+        Err(errors) => errors[0].kind,
+        result => panic!("Not aborted - result: {:?}", result),
+    }
+}
+
+#[test]
+fn max_steps() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        session.set_max_steps(Some(1000));
+        assert_eq!(
+            kind(&mut session, "while (true) {}"),
+            ErrorKind::OutOfFuel,
+            "{:?}",
+            backend
+        );
+        // 上限は実行ごとに数え直す
+        assert_eq!(session.eval("let a = 1;"), Ok(Value::Null));
+        assert_eq!(session.eval("a + 1;"), Ok(Value::Number(2.0)));
+
+        session.set_max_steps(None);
+        assert_eq!(
+            session.eval("let i = 0; while (i < 1000) { i = i + 1; }"),
+            Ok(Value::Number(1000.0)),
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn abort_not_caught() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        session.set_max_steps(Some(1000));
+        let src = "let caught = false;
+            let finished = false;
+            let f = function() {
+                while (true) {}
+            };
+            try {
+                f();
+            } catch (e) {
+                caught = true;
+            } finally {
+                finished = true;
+            }";
+        assert_eq!(kind(&mut session, src), ErrorKind::OutOfFuel);
+        assert_eq!(session.get("caught"), Some(Value::Bool(false)));
+        assert_eq!(session.get("finished"), Some(Value::Bool(false)));
+        assert_eq!(session.eval("1;"), Ok(Value::Number(1.0)), "{:?}", backend);
+    }
+}
+
+#[test]
+fn timeout() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        session.set_timeout(Some(Duration::from_millis(50)));
+        let src = "let f = function() { while (true) {} };";
+        assert_eq!(session.eval(src), Ok(Value::Null));
+        let errors = session.call("f", vec![]).unwrap_err();
+        assert_eq!(errors[0].kind, ErrorKind::Timeout, "{:?}", backend);
+        assert_eq!(errors[0].msg, "Timed out.");
+    }
+}
+
+#[test]
+fn interrupt() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        let handle = session.interrupt_handle();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.interrupt();
+        });
+        assert_eq!(
+            kind(&mut session, "while (true) {}"),
+            ErrorKind::Interrupted,
+            "{:?}",
+            backend
+        );
+        interrupter.join().unwrap();
+        // 割り込みは一度だけ効く
+        assert_eq!(
+            session.eval("let i = 0; while (i < 5000) { i = i + 1; }"),
+            Ok(Value::Number(5000.0))
+        );
+    }
+}
+
+#[test]
+fn interrupt_between_evals() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        // 実行していない間の割り込みは次の短い実行を止め、その後の実行には残らない
+        session.interrupt_handle().interrupt();
+        assert_eq!(
+            kind(&mut session, "1;"),
+            ErrorKind::Interrupted,
+            "{:?}",
+            backend
+        );
+        assert_eq!(session.eval("2;"), Ok(Value::Int(2)), "{:?}", backend);
+        assert_eq!(
+            session.eval("let i = 0; while (i < 5000) { i = i + 1; }"),
+            Ok(Value::Int(5000)),
+            "{:?}",
+            backend
+        );
+    }
+}
+
+#[test]
+fn timeout_short_eval() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        // 時間切れは最初のステップでも調べる
+        session.set_timeout(Some(Duration::ZERO));
+        assert_eq!(
+            kind(&mut session, "1;"),
+            ErrorKind::Timeout,
+            "{:?}",
+            backend
+        );
+        session.set_timeout(None);
+        assert_eq!(session.eval("2;"), Ok(Value::Int(2)), "{:?}", backend);
+    }
+}
@@ -0,0 +1,161 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::error::Error;
+
+#[test]
+fn for_loop() {
+    run::ok(
+        "let sum = 0; for (let i = 1; i <= 4; i = i + 1) { sum = sum + i; } sum;",
+        Value::Number(10.0),
+    );
+    run::ok(
+        "let i = 0; for (; i < 3;) { i = i + 1; } i;",
+        Value::Number(3.0),
+    );
+    // ループの値は最後に実行した本体の値
+    run::ok(
+        "for (let i = 0; i < 3; i = i + 1) { i * 2; }",
+        Value::Number(4.0),
+    );
+    run::ok("for (let i = 0; i < 0; i = i + 1) { i; }", Value::Null);
+}
+
+#[test]
+fn for_break_and_continue() {
+    run::ok(
+        "for (let i = 0; ; i = i + 1) { if (i == 5) { break i * 10; } }",
+        Value::Number(50.0),
+    );
+    // continueでも更新は実行する
+    run::ok(
+        "let sum = 0;
+        for (let i = 0; i < 5; i = i + 1) {
+            if (i == 2) { continue; }
+            sum = sum + i;
+        }
+        sum;",
+        Value::Number(8.0),
+    );
+}
+
+#[test]
+fn for_closures() {
+    // 繰り返しごとに別の変数を捕捉する
+    run::ok(
+        "let fs = [0, 0, 0];
+        for (let i = 0; i < 3; i = i + 1) {
+            fs[i] = function() { return i; };
+        }
+        fs[0]() + fs[1]() * 10 + fs[2]() * 100;",
+        Value::Number(210.0),
+    );
+    // 本体での変更は捕捉した変数に残るが、更新は次の繰り返しの変数に効く
+    run::ok(
+        "let f = 0;
+        for (let i = 0; i < 3; i = i + 1) {
+            if (i == 1) { f = function() { return i; }; i = 10; }
+        }
+        f();",
+        Value::Number(10.0),
+    );
+}
+
+#[test]
+fn for_scope() {
+    run::err1(
+        "for (let i = 0; i < 3; i = i + 1) {} i;",
+        1,
+        "i",
+        "Variable not defined.",
+    );
+    // 本体に直接書いたletは繰り返しごとに同じスコープで宣言し直す
+    run::ok("for (let i = 0; i < 1; i = i + 1) let x = i;", Value::Null);
+    run::err1(
+        "for (let i = 0; i < 3; i = i + 1) let x = i;",
+        1,
+        "x",
+        "Variable already defined.",
+    );
+    run::ok("for (x in [1, 2]) let y = x;", Value::Null);
+}
+
+#[test]
+fn for_in() {
+    run::ok(
+        "let sum = 0; for (x in [1, 2, 3]) { sum = sum + x; } sum;",
+        Value::Number(6.0),
+    );
+    run::ok(
+        r#"let keys = ""; for (k in {"a": 1, "b": 2}) { keys = keys + k; } keys;"#,
+        Value::String("ab".to_string()),
+    );
+    run::ok(
+        "for (x in [1, 2, 3]) { if (x == 2) { break x * 10; } }",
+        Value::Number(20.0),
+    );
+    run::ok(
+        "let sum = 0; for (x in [1, 2, 3]) { if (x == 2) { continue; } sum = sum + x; } sum;",
+        Value::Number(4.0),
+    );
+    run::ok("for (x in []) { x; }", Value::Null);
+    // 回し始めたときの要素を回す
+    run::ok(
+        "let a = [1, 2]; let n = 0; for (x in a) { a[0] = 10; n = n + x; } n;",
+        Value::Number(3.0),
+    );
+}
+
+#[test]
+fn for_in_closures() {
+    run::ok(
+        r#"let fs = {};
+        for (x in ["a", "b"]) {
+            fs[x] = function() { return x; };
+        }
+        fs["a"]() + fs["b"]();"#,
+        Value::String("ab".to_string()),
+    );
+}
+
+#[test]
+fn for_in_errors() {
+    run::err1(
+        "for (x in 1) {}",
+        1,
+        "in",
+        "Only arrays and dictionaries can be iterated.",
+    );
+    run::err(
+        "for (x in [1]) {} x;",
+        vec![Error::new(1, "x", "Variable not defined.")],
+    );
+}
+
+#[test]
+fn nested_loops() {
+    run::ok(
+        "let n = 0;
+        for (x in [1, 2, 3]) {
+            for (let i = 0; i < x; i = i + 1) {
+                if (i == 1) { break; }
+                n = n + 1;
+            }
+        }
+        n;",
+        Value::Number(3.0),
+    );
+    run::ok(
+        "let f = function() {
+            for (x in [1, 2, 3]) {
+                try {
+                    if (x == 2) { return x; }
+                } finally {
+                    x;
+                }
+            }
+        };
+        f();",
+        Value::Number(2.0),
+    );
+}
@@ -0,0 +1,118 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::error::Error;
+
+#[test]
+fn import() {
+    run::ok(
+        r#"import "tests/modules/math.jsc" as math; math.add(1, 2);"#,
+        Value::Number(3.0),
+    );
+}
+
+#[test]
+fn live_binding() {
+    run::ok(
+        r#"import "tests/modules/math.jsc" as math;
+        math.increment();
+        math.increment();
+        math.counter;"#,
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn evaluated_once() {
+    run::ok(
+        r#"import "tests/modules/math.jsc" as m1;
+        import "tests/modules/math.jsc" as m2;
+        m1.increment();
+        [m1 == m2, m2.counter];"#,
+        run::eval("[true, 1];"),
+    );
+}
+
+#[test]
+fn relative_path() {
+    run::ok(
+        r#"import "tests/modules/uses_sub.jsc" as m; m.value;"#,
+        Value::Number(11.0),
+    );
+}
+
+#[test]
+fn not_exported() {
+    run::err1(
+        r#"import "tests/modules/math.jsc" as math; math.secret;"#,
+        1,
+        ".",
+        "Name not exported from module.",
+    );
+    run::err1(
+        r#"import "tests/modules/math.jsc" as math; math.counter = 1;"#,
+        1,
+        ".",
+        "Cannot assign to a module member.",
+    );
+}
+
+#[test]
+fn cycle() {
+    run::err(
+        r#"import "tests/modules/cycle_a.jsc" as a;"#,
+        vec![Error::new(
+            1,
+            "import",
+            "Import cycle detected: tests/modules/cycle_a.jsc -> tests/modules/cycle_b.jsc -> tests/modules/cycle_a.jsc.",
+        )
+        .in_file("tests/modules/cycle_b.jsc")],
+    );
+}
+
+#[test]
+fn error_in_module() {
+    run::err(
+        r#"import "tests/modules/runtime_error.jsc" as m;"#,
+        vec![Error::new(2, "undefined_variable", "Variable not defined.")
+            .in_file("tests/modules/runtime_error.jsc")],
+    );
+}
+
+#[test]
+fn errors_in_module() {
+    let file = "tests/modules/syntax_errors.jsc";
+    run::err(
+        r#"import "tests/modules/syntax_errors.jsc" as m;"#,
+        vec![
+            Error::new(1, ";", "Expression expected, found `;`").in_file(file),
+            Error::new(3, ";", "Expression expected, found `;`").in_file(file),
+        ],
+    );
+}
+
+#[test]
+fn not_found() {
+    run::err(
+        r#"import "tests/modules/nothing.jsc" as m;"#,
+        vec![Error::new(
+            1,
+            "import",
+            "Cannot open module 'tests/modules/nothing.jsc': No such file or directory (os error 2).",
+        )],
+    );
+}
+
+#[test]
+fn parse_error() {
+    run::err1("import math;", 1, "math", "Module path expected.");
+    run::err1(r#"import "a.jsc" math;"#, 1, "math", "'as' expected.");
+    run::err1("export 1;", 1, "1", "Let expected after export.");
+    run::err(
+        "{ export let a = 1; }",
+        vec![
+            Error::new(1, "export", "Export is only allowed at top level."),
+            Error::new(1, "}", "Expression expected, found `}`"),
+        ],
+    );
+}
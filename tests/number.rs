@@ -4,10 +4,67 @@ use jasc::ast::Value;
 
 #[test]
 fn simple_number() {
-    run::ok("123;", Value::Number(123.0))
+    run::ok("123;", Value::Int(123))
 }
 
 #[test]
 fn number_no_semicolon() {
     run::err1("123", 1, "end", "Semicolon expected.");
 }
+
+#[test]
+fn decimal_number() {
+    run::ok("2.5;", Value::Number(2.5));
+    run::ok("0.5 + 0.25;", Value::Number(0.75));
+    run::ok("1.5e-3;", Value::Number(0.0015));
+    run::ok("2E3;", Value::Number(2000.0));
+    run::ok("1e+2;", Value::Number(100.0));
+}
+
+#[test]
+fn radix_number() {
+    run::ok("0xff;", Value::Int(255));
+    run::ok("0XFF;", Value::Int(255));
+    run::ok("0o17;", Value::Int(15));
+    run::ok("0b1010;", Value::Int(10));
+}
+
+#[test]
+fn digit_separator() {
+    run::ok("1_000_000;", Value::Int(1000000));
+    run::ok("0x1_F;", Value::Int(31));
+    run::ok("1_0.2_5e1_0;", Value::Number(10.25e10));
+}
+
+#[test]
+fn dot_after_number() {
+    // 小数点の後に数字がなければ小数にしない
+    run::err1("1.;", 1, ";", "Property name expected.");
+}
+
+#[test]
+fn malformed_number() {
+    run::err1("1e;", 1, "1e", "Exponent digits expected.");
+    run::err1("1e+;", 1, "1e+", "Exponent digits expected.");
+    run::err1("0xZ;", 1, "0xZ", "Invalid digit in hexadecimal literal.");
+    run::err1("0b102;", 1, "0b102", "Invalid digit in binary literal.");
+    run::err1("0o8;", 1, "0o8", "Invalid digit in octal literal.");
+    run::err1("0x;", 1, "0x", "Hexadecimal digits expected.");
+    run::err1("12abc;", 1, "12abc", "Invalid character in number literal.");
+    run::err1(
+        "9223372036854775808;",
+        1,
+        "9223372036854775808",
+        "Integer literal too large.",
+    );
+    run::err1(
+        "0x1_0000_0000_0000_0000;",
+        1,
+        "0x1_0000_0000_0000_0000",
+        "Integer literal too large.",
+    );
+    for src in ["1__0;", "1_;", "1_.5;", "0x_1;"] {
+        let location = src.trim_end_matches(';');
+        run::err1(src, 1, location, "Digit separator must be between digits.");
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn repl(input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jasc"))
+        .arg("--repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start jasc");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn keeps_state() {
+    let (out, err) = repl("let a = 1;\na + 1;\nprint a;\na = 5;\n");
+    assert_eq!(out, "2\n1\n5\n");
+    assert_eq!(err, "");
+}
+
+#[test]
+fn continues_incomplete_input() {
+    let (out, err) = repl("let f = function (a) {\n  a * 2;\n};\nf(\n21\n);\n\"multi\nline\";\n");
+    assert_eq!(out, "42\nmulti\nline\n");
+    assert_eq!(err, "");
+}
+
+#[test]
+fn continues_unterminated_comment() {
+    let (out, err) = repl("/* open\nstill */ print 1;\n/* a /* b */\n*/ 2;\n");
+    assert_eq!(out, "1\n2\n");
+    assert_eq!(err, "");
+}
+
+#[test]
+fn survives_runtime_error() {
+    let (out, err) = repl("let a = 1;\nb;\na;\n1 +;\na + 1;\n");
+    assert_eq!(out, "1\n2\n");
+    assert_eq!(
+        err,
+        "[line 1:1] Error at 'b': Variable not defined.\n[line 1:4] Error at ';': Expression expected, found `;`\n"
+    );
+}
+
+#[test]
+fn reports_unfinished_input() {
+    let (out, err) = repl("print 1;\n{ print 2;\n");
+    assert_eq!(out, "1\n");
+    assert_eq!(err, "[line 2:1] Error at 'end': Right brace expected.\n");
+}
@@ -0,0 +1,183 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::error::Error;
+
+#[test]
+fn closure_binds_statically() {
+    run::ok(
+        r#"let a = "global";
+        let f;
+        {
+            f = function() { return a; };
+            let a = "local";
+        }
+        f();"#,
+        Value::String("global".to_string()),
+    );
+    run::ok(
+        "let make = function() { let n = 0; return function() { n = n + 1; return n; }; };
+        let counter = make();
+        counter();
+        counter();",
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn shadowing() {
+    run::ok("let a = 1; { let a = a + 1; a; }", Value::Number(2.0));
+    run::ok("let a = 1; { let a = 2; } a;", Value::Number(1.0));
+    run::ok(
+        "let f = function(a) { let a = 2; return a; }; f(1);",
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn recursion() {
+    run::ok(
+        "{ let f = function(n) { return n == 0 ? 0 : n + f(n - 1); }; f(3); }",
+        Value::Number(6.0),
+    );
+    // 関数の中からは後で宣言されるトップレベルの変数を参照できる
+    run::ok(
+        "let even = function(n) { return n == 0 ? true : odd(n - 1); };
+        let odd = function(n) { return n == 0 ? false : even(n - 1); };
+        even(4);",
+        Value::Bool(true),
+    );
+}
+
+#[test]
+fn undefined_before_running() {
+    run::err1("let a = 1; a = a + b;", 1, "b", "Variable not defined.");
+    run::err1("print a; let a = 1;", 1, "a", "Variable not defined.");
+    run::err1("{ let a = 1; } a = 2;", 1, "a", "Variable not defined.");
+    run::err1(
+        "let f = function() { return g; };",
+        1,
+        "g",
+        "Variable not defined.",
+    );
+    run::err(
+        "x; while (true) { y = 1; }",
+        vec![
+            Error::new(1, "x", "Variable not defined."),
+            Error::new(1, "y", "Variable not defined."),
+        ],
+    );
+}
+
+#[test]
+fn duplicate_let() {
+    run::err(
+        "let a = 1;\nlet a = 2;",
+        vec![Error::new(2, "a", "Variable already defined.").with_note(
+            1,
+            "Variable declared here.",
+            None,
+        )],
+    );
+    run::err(
+        "{ let b; let b; }",
+        vec![Error::new(1, "b", "Variable already defined.").with_note(
+            1,
+            "Variable declared here.",
+            None,
+        )],
+    );
+    run::err(
+        "let f = function(a, a) {};",
+        vec![Error::new(1, "a", "Variable already defined.").with_note(
+            1,
+            "Variable declared here.",
+            None,
+        )],
+    );
+}
+
+#[test]
+fn break_outside_loop() {
+    run::err1("if (true) break;", 1, "break", "Break from top level");
+    run::err1("{ continue; }", 1, "continue", "Continue from top level");
+    run::err1(
+        "while (true) { let f = function() { break; }; }",
+        1,
+        "break",
+        "Break outside loop",
+    );
+    run::err1(
+        "let f = function() { continue; };",
+        1,
+        "continue",
+        "Continue outside loop",
+    );
+    run::ok(
+        "let f = function() { while (true) { break 1; } }; f();",
+        Value::Number(1.0),
+    );
+}
+
+// ifやwhileの本体に直接書いた宣言は外側のスコープに入る
+#[test]
+fn declaration_in_body() {
+    run::ok("if (true) let a = 1; a;", Value::Number(1.0));
+    run::ok("if (false) 0; else let a = 2; a;", Value::Number(2.0));
+    run::ok(
+        "let f = function() { return a; }; if (true) let a = 3; f();",
+        Value::Number(3.0),
+    );
+    run::ok(
+        "let i = 0; while (i < 1) let a = i = i + 1; a;",
+        Value::Number(1.0),
+    );
+    run::ok(
+        "let f = function(n) {
+            if (n > 0) let g = function(k) { return k == 0 ? 0 : k + g(k - 1); };
+            return g(n);
+        };
+        f(3);",
+        Value::Number(6.0),
+    );
+    run::ok(
+        "{ let i = 0; while ((i = i + 1) < 2) let c = function() { return i; }; i = 10; c(); }",
+        Value::Number(10.0),
+    );
+    run::err1("if (false) let a = 1; a;", 1, "a", "Variable not defined.");
+    run::err1(
+        "let f = function() { let i = 0; while (i < 2) let a = i = i + 1; }; f();",
+        1,
+        "a",
+        "Variable already defined.",
+    );
+    run::err(
+        "let a = 0; if (true) let a = 1;",
+        vec![Error::new(1, "a", "Variable already defined.").with_note(
+            1,
+            "Variable declared here.",
+            None,
+        )],
+    );
+}
+
+#[test]
+fn top_level_return() {
+    run::err1("return 1; 2;", 1, "return", "Return outside function.");
+    run::err1(
+        "if (true) { return; }",
+        1,
+        "return",
+        "Return outside function.",
+    );
+    run::err1(
+        "while (true) { let f = function() { return 1; }; return 2; }",
+        1,
+        "return",
+        "Return outside function.",
+    );
+    run::ok(
+        "let f = function() { { return 1; } }; f();",
+        Value::Number(1.0),
+    );
+}
@@ -1,24 +1,63 @@
 use jasc::ast;
 use jasc::ast::Value;
-use jasc::error::Error;
+use jasc::error::{Error, Note};
 use jasc::token;
+use jasc::Backend;
+
+// ツリーを辿るインタプリタとVMで同じ結果になることを確かめる
+const BACKENDS: [Backend; 2] = [Backend::TreeWalk, Backend::Vm];
 
 #[allow(dead_code)]
 pub fn ok(src: &str, expected: Value) {
-    let result = jasc::run(src);
-    match result {
-        Ok(value) => assert_eq!(value, expected),
-        _ => panic!("Failed - result: {:?}", result),
+    for backend in BACKENDS {
+        let result = jasc::run_with(src, backend);
+        match result {
+            Ok(value) => assert_eq!(value, expected, "{:?}", backend),
+            _ => panic!("Failed ({:?}) - result: {:?}", backend, result),
+        }
     }
 }
 
 #[allow(dead_code)]
-pub fn err(src: &str, vexpected: Vec<Error>) {
-    let result = jasc::run(src);
-    match result {
-        Err(ve) => assert_eq!(ve, vexpected),
-        _ => panic!("Failed - result: {:?}", result),
+pub fn eval(src: &str) -> Value {
+    let value = match jasc::run(src) {
+        Ok(value) => value,
+        result => panic!("Failed - result: {:?}", result),
     };
+    match jasc::run_with(src, Backend::Vm) {
+        Ok(vm) => assert_eq!(format!("{}", vm), format!("{}", value)),
+        result => panic!("Failed (Vm) - result: {:?}", result),
+    }
+    value
+}
+
+// 位置の範囲とスタックトレースは別にテストする
+#[allow(dead_code)]
+pub fn without_span(errors: Vec<Error>) -> Vec<Error> {
+    errors
+        .into_iter()
+        .map(|e| Error {
+            span: None,
+            trace: None,
+            notes: e
+                .notes
+                .into_iter()
+                .map(|note| Note { span: None, ..note })
+                .collect(),
+            ..e
+        })
+        .collect()
+}
+
+#[allow(dead_code)]
+pub fn err(src: &str, vexpected: Vec<Error>) {
+    for backend in BACKENDS {
+        let result = jasc::run_with(src, backend);
+        match result {
+            Err(ve) => assert_eq!(without_span(ve), vexpected, "{:?}", backend),
+            _ => panic!("Failed ({:?}) - result: {:?}", backend, result),
+        };
+    }
 }
 
 #[allow(dead_code)]
@@ -48,7 +87,7 @@ pub fn scan(src: &str, expected: &str) {
 pub fn scan_err(src: &str, vexpected: Vec<Error>) {
     let result = jasc::scan(src);
     match result {
-        Err(ve) => assert_eq!(ve, vexpected),
+        Err(ve) => assert_eq!(without_span(ve), vexpected),
         _ => panic!("Failed - result: {:?}", result),
     };
 }
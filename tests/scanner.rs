@@ -1,4 +1,5 @@
 use jasc::ast::Value;
+use jasc::error::{Error, ErrorKind};
 
 mod run;
 
@@ -18,3 +19,38 @@ fn comment() {
     run::ok("12 + // comment\n 34; ", Value::Number(46.0));
     run::err1("12 + // comment\n 34", 2, "end", "Semicolon expected.");
 }
+
+#[test]
+fn block_comment() {
+    run::ok("12 /* comment */ + 34;", Value::Int(46));
+    run::ok("12 /* a\n b */ + /**/ 34;", Value::Int(46));
+    run::ok("/* outer /* inner */ still comment */ 1;", Value::Int(1));
+    run::ok("1 /* */ / 2 /***/;", Value::Number(0.5));
+    // 行番号はコメントの中の改行も数える
+    run::err1("/*\n\n*/ 12 +\n 34", 4, "end", "Semicolon expected.");
+}
+
+#[test]
+fn unterminated_block_comment() {
+    let unterminated =
+        |line| Error::new(line, "", "Unterminated comment.").with_kind(ErrorKind::Unterminated);
+    run::err("1;\n/* a\n /* b */\n", vec![unterminated(2)]);
+    run::err(
+        "@ /* a",
+        vec![Error::new(1, "@", "Unexpected character."), unterminated(1)],
+    );
+}
+
+#[test]
+fn doc_comment() {
+    let tokens = jasc::scan("/// Adds one.\n///\n///   Returns a number.\nlet f;").unwrap();
+    assert_eq!(
+        tokens[0].doc.as_deref(),
+        Some("Adds one.\n\n  Returns a number.")
+    );
+    assert_eq!(tokens[1].doc, None);
+
+    // let以外のトークンの前のものや`////`は捨てる
+    let tokens = jasc::scan("/// not for print\nprint 1;\n//// plain\nlet a;").unwrap();
+    assert!(tokens.iter().all(|token| token.doc.is_none()));
+}
@@ -0,0 +1,91 @@
+use jasc::ast::Value;
+use jasc::error::Error;
+use jasc::session::Session;
+use jasc::token::Span;
+
+fn span(start: usize, column: usize) -> Span {
+    Span {
+        start,
+        end: start + 1,
+        column,
+        end_line: 1,
+        end_column: column + 1,
+    }
+}
+
+#[test]
+fn eval_keeps_state() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("let a = 1;"), Ok(Value::Null));
+    assert_eq!(session.eval("a = a + 1;"), Ok(Value::Number(2.0)));
+    assert_eq!(session.eval("a * 10;"), Ok(Value::Number(20.0)));
+}
+
+#[test]
+fn state_survives_errors() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("let a = 1;"), Ok(Value::Null));
+    assert_eq!(
+        session.eval("{ let b = 2; b + c; }"),
+        Err(vec![
+            Error::new(1, "c", "Variable not defined.").with_span(span(17, 18))
+        ])
+    );
+    assert_eq!(session.eval("a;"), Ok(Value::Number(1.0)));
+    assert_eq!(
+        session.eval("b;"),
+        Err(vec![
+            Error::new(1, "b", "Variable not defined.").with_span(span(0, 1))
+        ])
+    );
+}
+
+#[test]
+fn get_and_set() {
+    let mut session = Session::new();
+    session.eval("let a = 1 + 2;").unwrap();
+    assert_eq!(session.get("a"), Some(Value::Int(3)));
+    assert_eq!(session.get("nothing"), None);
+
+    session.set("a", Value::Int(10));
+    session.set("name", Value::String("jasc".to_string()));
+    assert_eq!(
+        session.eval(r#"name + " " + a;"#),
+        Ok(Value::String("jasc 10".to_string()))
+    );
+}
+
+#[test]
+fn call() {
+    let mut session = Session::new();
+    session
+        .eval("let add = function (a, b) { a + b; };")
+        .unwrap();
+    assert_eq!(
+        session.call("add", vec![Value::Number(1.0), Value::Number(2.0)]),
+        Ok(Value::Number(3.0))
+    );
+    assert_eq!(
+        session.call("len", vec![Value::String("abc".to_string())]),
+        Ok(Value::Number(3.0))
+    );
+    assert_eq!(
+        session.call("add", vec![]),
+        Err(vec![Error::new(
+            0,
+            "add",
+            "Number of the arguments does not match."
+        )])
+    );
+    assert_eq!(
+        session.call("nothing", vec![]),
+        Err(vec![Error::new(0, "nothing", "Variable not defined.")])
+    );
+}
+
+#[test]
+fn define_native() {
+    let mut session = Session::new();
+    session.define_native("answer", 0, |_| Ok(Value::Number(42.0)));
+    assert_eq!(session.eval("answer() + 1;"), Ok(Value::Number(43.0)));
+}
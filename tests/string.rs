@@ -0,0 +1,65 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::error::{Error, ErrorKind};
+
+#[test]
+fn literal() {
+    run::ok(r#""abc";"#, Value::String("abc".to_string()));
+    run::ok(r#""";"#, Value::String("".to_string()));
+    run::ok(r#""日本語";"#, Value::String("日本語".to_string()));
+}
+
+#[test]
+fn escape() {
+    run::ok(
+        r#""a\nb\tc\"d\\e\r\0";"#,
+        Value::String("a\nb\tc\"d\\e\r\0".to_string()),
+    );
+    run::ok(r#""\u{41}\u{3042}";"#, Value::String("Aあ".to_string()));
+}
+
+#[test]
+fn multi_line() {
+    run::ok("\"a\nb\";", Value::String("a\nb".to_string()));
+    run::err1("\"a\nb\"\n@", 3, "@", "Unexpected character.");
+}
+
+#[test]
+fn concatenation() {
+    run::ok(r#""abc" + "def";"#, Value::String("abcdef".to_string()));
+    run::ok(r#""a" + 1 + 2;"#, Value::String("a12".to_string()));
+    run::ok(r#"1 + 2 + "a";"#, Value::String("3a".to_string()));
+    run::ok(r#""a" + true;"#, Value::String("atrue".to_string()));
+}
+
+#[test]
+fn comparison() {
+    run::ok(r#""abc" == "abc";"#, Value::Bool(true));
+    run::ok(r#""abc" != "abd";"#, Value::Bool(true));
+    run::ok(r#""abc" < "abd";"#, Value::Bool(true));
+    run::ok(r#""b" >= "abc";"#, Value::Bool(true));
+    run::ok(r#""1" == 1;"#, Value::Bool(false));
+}
+
+#[test]
+fn unterminated() {
+    let unterminated =
+        || vec![Error::new(1, "", "Unterminated string.").with_kind(ErrorKind::Unterminated)];
+    run::err(r#""abc"#, unterminated());
+    run::err("\"abc\n", unterminated());
+}
+
+#[test]
+fn invalid_escape() {
+    run::err1(r#""a\qb";"#, 1, "\\q", "Invalid escape sequence.");
+    run::err1(r#""\u41";"#, 1, "\\u", "Expected '{' in unicode escape.");
+    run::err1(
+        r#""\u{41";"#,
+        1,
+        "\\u{41",
+        "Expected '}' in unicode escape.",
+    );
+    run::err1(r#""\u{}";"#, 1, "\\u{}", "Invalid unicode escape.");
+    run::err1(r#""\u{d800}";"#, 1, "\\u{d800}", "Invalid unicode escape.");
+}
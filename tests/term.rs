@@ -46,6 +46,7 @@ fn addition_no_semicolon_after_3() {
 #[test]
 fn add_missing_right() {
     run::err1("12+;", 1, ";", "Expression expected, found `;`");
+    run::err1("12+", 1, "end", "Expression expected, found `end`");
 }
 
 #[test]
@@ -3,7 +3,8 @@ use jasc::error::Error;
 
 #[test]
 fn number() {
-    run::scan("123", "(Number(123.0) '123' 1)\n(EOF '' 1)\n");
+    run::scan("123", "(Int(123) '123' 1)\n(EOF '' 1)\n");
+    run::scan("1.5", "(Number(1.5) '1.5' 1)\n(EOF '' 1)\n");
 }
 
 #[test]
@@ -32,10 +33,30 @@ fn unexpected_charcter() {
 #[test]
 fn multi_errors_multi_lines() {
     run::scan_err(
-        "@\n^)",
+        "@\n$)",
         vec![
             Error::new(1, "@", "Unexpected character."),
-            Error::new(2, "^", "Unexpected character."),
+            Error::new(2, "$", "Unexpected character."),
         ],
     );
 }
+
+#[test]
+fn string() {
+    run::scan(
+        "\"a\\nb\"",
+        "(String(\"a\\nb\") '\"a\\nb\"' 1)\n(EOF '' 1)\n",
+    );
+    run::scan(
+        "\"a\nb\" (",
+        "(String(\"a\\nb\") '\"a\nb\"' 1)\n(LeftParen '(' 2)\n(EOF '' 2)\n",
+    );
+}
+
+#[test]
+fn exception_keywords() {
+    run::scan(
+        "try catch finally throw",
+        "(Try 'try' 1)\n(Catch 'catch' 1)\n(Finally 'finally' 1)\n(Throw 'throw' 1)\n(EOF '' 1)\n",
+    );
+}
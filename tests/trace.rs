@@ -0,0 +1,205 @@
+use jasc::ast::Value;
+use jasc::error::Call;
+use jasc::session::Session;
+use jasc::Backend;
+
+const BACKENDS: [Backend; 2] = [Backend::TreeWalk, Backend::Vm];
+
+fn call(function: Option<&str>, line: usize) -> Call {
+    Call {
+        function: function.map(|name| name.to_string()),
+        line,
+    }
+}
+
+fn trace(src: &str) -> Vec<Call> {
+    let mut traces = BACKENDS
+        .iter()
+        .map(|backend| match jasc::run_with(src, *backend) {
+            Err(errors) => errors[0].trace().to_vec(),
+            result => panic!("Failed ({:?}) - result: {:?}", backend, result),
+        });
+    let trace = traces.next().unwrap();
+    for other in traces {
+        assert_eq!(other, trace);
+    }
+    trace
+}
+
+#[test]
+fn nested_calls() {
+    assert_eq!(
+        trace(
+            "let inner = function(x) {
+                x - true;
+            };
+            let outer = function() {
+                inner(1);
+            };
+            outer();"
+        ),
+        vec![call(Some("inner"), 5), call(Some("outer"), 7)]
+    );
+}
+
+#[test]
+fn top_level_error() {
+    assert_eq!(trace("1 - true;"), vec![]);
+}
+
+#[test]
+fn anonymous_function() {
+    assert_eq!(
+        trace("let apply = function(f) { f(); };\napply(function() { throw 1; });"),
+        vec![call(None, 1), call(Some("apply"), 2)]
+    );
+}
+
+#[test]
+fn recursion() {
+    assert_eq!(
+        trace("let f = function(n) { if (n == 0) { [][0]; } f(n - 1); };\nf(2);"),
+        vec![call(Some("f"), 1), call(Some("f"), 1), call(Some("f"), 2)]
+    );
+}
+
+#[test]
+fn local_function() {
+    assert_eq!(
+        trace("{\n  let g = function() { 1(); };\n  g();\n}"),
+        vec![call(Some("g"), 3)]
+    );
+}
+
+// 捕まえたエラーを投げ直すと、投げ直したところからの呼び出しも加わる
+#[test]
+fn rethrow() {
+    assert_eq!(
+        trace(
+            "let fail = function() { 1(); };
+            let retry = function() { try { fail(); } catch (e) { throw e; } };
+            retry();"
+        ),
+        vec![call(Some("fail"), 2), call(Some("retry"), 3)]
+    );
+}
+
+#[test]
+fn host_call() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        session
+            .eval("let check = function(x) { if (x) { 1(); } x; };")
+            .unwrap();
+        assert_eq!(
+            session.call("check", vec![Value::Bool(false)]),
+            Ok(Value::Bool(false))
+        );
+        let errors = session.call("check", vec![Value::Bool(true)]).unwrap_err();
+        assert_eq!(
+            errors[0].trace(),
+            vec![call(Some("check"), 0)],
+            "{:?}",
+            backend
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1:37] Error at '(': Callee is not a function.\n  in check"
+        );
+    }
+}
+
+#[test]
+fn stack_overflow() {
+    for backend in BACKENDS {
+        let mut session = Session::with_backend(backend);
+        session.set_max_depth(50);
+        session
+            .eval(
+                "let depth = 0;
+                let f = function() {
+                    depth = depth + 1;
+                    f();
+                };",
+            )
+            .unwrap();
+        let errors = session.eval("f();").unwrap_err();
+        assert_eq!(errors[0].msg, "Stack overflow.", "{:?}", backend);
+        assert_eq!(errors[0].line, 4, "{:?}", backend);
+        assert_eq!(errors[0].trace().len(), 50, "{:?}", backend);
+        assert_eq!(session.get("depth"), Some(Value::Number(50.0)));
+
+        // 捕まえれば続けられる
+        assert_eq!(
+            session.eval(
+                "depth = 0;
+                try { f(); } catch (e) { e.message; }"
+            ),
+            Ok(Value::String("Stack overflow.".to_string())),
+            "{:?}",
+            backend
+        );
+        assert_eq!(session.eval("depth;"), Ok(Value::Number(50.0)));
+    }
+}
+
+// 標準の大きさのスタックのスレッドで、既定の設定のまま実行する
+fn on_default_thread<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(jasc::session::DEFAULT_STACK_SIZE)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn default_max_depth() {
+    // VMはネイティブのスタックを使わないので既定の上限まで再帰できる
+    let depth = on_default_thread(|| {
+        let src = "let f = function(n) { return f(n + 1); }; f(0);";
+        jasc::run_with(src, Backend::Vm).unwrap_err()[0]
+            .trace()
+            .len()
+    });
+    assert_eq!(depth, jasc::session::DEFAULT_MAX_DEPTH);
+
+    // 大きいスタックを使えると伝えれば、木を辿る実行方式も上限まで再帰できる
+    let size = 64 * 1024 * 1024;
+    let runner = std::thread::Builder::new()
+        .stack_size(size)
+        .spawn(move || {
+            let mut session = Session::with_backend(Backend::TreeWalk);
+            session.set_stack_size(size);
+            let src = "let f = function(n) { return f(n + 1); }; f(0);";
+            session.eval(src).unwrap_err()[0].trace().len()
+        })
+        .unwrap();
+    assert_eq!(runner.join().unwrap(), jasc::session::DEFAULT_MAX_DEPTH);
+}
+
+#[test]
+fn native_stack_overflow() {
+    // 1段ごとにネイティブのスタックを多く使う再帰も、溢れる前にエラーにする
+    for backend in BACKENDS {
+        let errors = on_default_thread(move || {
+            Session::with_backend(backend)
+                .eval(
+                    "let f = function(n) {
+                        if (n > -1) {
+                            let x = [f(n + 1)];
+                            return x[0];
+                        }
+                    };
+                    f(0);",
+                )
+                .unwrap_err()
+        });
+        assert_eq!(errors[0].msg, "Stack overflow.", "{:?}", backend);
+        assert!(
+            errors[0].trace().len() <= jasc::session::DEFAULT_MAX_DEPTH,
+            "{:?}",
+            backend
+        );
+    }
+}
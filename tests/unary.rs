@@ -11,3 +11,27 @@ fn unary_minus() {
 fn unary_minus_missing_right() {
     run::err1("-;", 1, ";", "Expression expected, found `;`");
 }
+
+#[test]
+fn unary_nested() {
+    run::ok("- -12;", Value::Int(12));
+    run::ok("let f = function(x) { x; }; -f(3);", Value::Int(-3));
+    run::ok("-[1, 2][1];", Value::Int(-2));
+}
+
+#[test]
+fn logical_not() {
+    run::ok("!true;", Value::Bool(false));
+    run::ok("!0;", Value::Bool(true));
+    run::ok("!\"\";", Value::Bool(true));
+    run::ok("!![1];", Value::Bool(true));
+    run::ok("!1 == false;", Value::Bool(true));
+}
+
+#[test]
+fn bit_not() {
+    run::ok("~0;", Value::Int(-1));
+    run::ok("~5.0;", Value::Int(-6));
+    run::err1("~1.5;", 1, "~", "Operand must be an integer.");
+    run::err1("~\"a\";", 1, "~", "Operand must be an integer.");
+}
@@ -0,0 +1,94 @@
+mod run;
+
+use jasc::ast::Value;
+
+#[test]
+fn compound_assignment() {
+    run::ok("let a = 1; a += 2;", Value::Int(3));
+    run::ok("let a = 1; a -= 3; a;", Value::Int(-2));
+    run::ok("let a = 3; a *= a; a;", Value::Int(9));
+    run::ok("let a = 7; a /= 2; a;", Value::Number(3.5));
+    run::ok("let a = 7; a %= 4; a;", Value::Int(3));
+    run::ok("let s = \"a\"; s += 1; s;", Value::String("a1".to_string()));
+    run::ok("let a = 1; let b = 2; a += b += 3; a;", Value::Int(6));
+}
+
+#[test]
+fn increment() {
+    run::ok("let i = 0; i++;", Value::Int(0));
+    run::ok("let i = 0; i++; i;", Value::Int(1));
+    run::ok("let i = 0; ++i;", Value::Int(1));
+    run::ok("let i = 0; i--;", Value::Int(0));
+    run::ok("let i = 0; --i;", Value::Int(-1));
+    run::ok("let i = 1; i++ + ++i;", Value::Int(4));
+    run::ok("let x = 0.5; x++; x;", Value::Number(1.5));
+}
+
+#[test]
+fn local_and_upvalue() {
+    run::ok(
+        "let f = function() {
+            let n = 1;
+            let g = function() { n *= 10; n++; };
+            g();
+            g() + n;
+        };
+        f();",
+        Value::Int(221),
+    );
+    run::ok(
+        "let sum = 0;
+        for (let i = 0; i < 5; i++) { sum += i; }
+        sum;",
+        Value::Int(10),
+    );
+}
+
+#[test]
+fn index_target() {
+    run::ok("let a = [1, 2]; a[1] += 10; a[1];", Value::Int(12));
+    run::ok("let a = [1, 2]; a[0]++;", Value::Int(1));
+    run::ok("let a = [1, 2]; a[0]++; a[0];", Value::Int(2));
+    run::ok("let a = [1, 2]; --a[1];", Value::Int(1));
+    run::ok("let d = {n: 1}; d.n *= 5; d[\"n\"];", Value::Int(5));
+    run::ok("let d = {n: 1}; d.n++ + d.n;", Value::Int(3));
+}
+
+#[test]
+fn evaluated_once() {
+    // 配列と添字の式は一度だけ評価する
+    run::ok(
+        "let calls = 0;
+        let a = [0, 0];
+        let f = function() { calls++; a; };
+        let i = function() { calls++; 1; };
+        f()[i()] += 5;
+        f()[i()]++;
+        [calls, a[1]];",
+        run::eval("[4, 6];"),
+    );
+}
+
+#[test]
+fn update_error() {
+    run::err1(
+        "let s = \"a\"; s -= 1;",
+        1,
+        "-=",
+        "Operands must be two numbers.",
+    );
+    run::err1(
+        "let b = true; b++;",
+        1,
+        "++",
+        "Operands must be two numbers.",
+    );
+    run::err1("let a = [1]; a[1] += 1;", 1, "[", "Index out of range.");
+    run::err1("x++;", 1, "x", "Variable not defined.");
+    run::err1(
+        "let m = 9223372036854775807; m++;",
+        1,
+        "++",
+        "Integer overflow.",
+    );
+}
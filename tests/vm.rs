@@ -0,0 +1,111 @@
+mod run;
+
+use jasc::ast::Value;
+use jasc::session::Session;
+use jasc::Backend;
+
+#[test]
+fn shared_upvalue() {
+    run::ok(
+        "let make = function() {
+            let n = 0;
+            let inc = function() { n = n + 1; };
+            let get = function() { n; };
+            [inc, get];
+        };
+        let fs = make();
+        fs[0]();
+        fs[0]();
+        fs[1]();",
+        Value::Number(2.0),
+    );
+}
+
+#[test]
+fn nested_upvalue() {
+    run::ok(
+        "let a = function(x) {
+            function() { function() { x = x * 2; x; }; };
+        };
+        let f = a(3)();
+        f();
+        f();",
+        Value::Number(12.0),
+    );
+}
+
+#[test]
+fn closure_in_loop() {
+    run::ok(
+        "let fs = [0, 0, 0];
+        let i = 0;
+        while (i < 3) {
+            let j = i;
+            fs[i] = function() { j * 10; };
+            i = i + 1;
+        }
+        fs[0]() + fs[1]() + fs[2]();",
+        Value::Number(30.0),
+    );
+}
+
+#[test]
+fn closed_after_break() {
+    run::ok(
+        "let f = 0;
+        let i = 0;
+        while (true) {
+            let k = i;
+            f = function() { k; };
+            i = i + 1;
+            if (i == 2) { break; }
+        }
+        f();",
+        Value::Number(1.0),
+    );
+}
+
+#[test]
+fn loop_value() {
+    run::ok(
+        "let i = 0; while (i < 3) { i = i + 1; { let x = i; x * 2; } }",
+        Value::Number(6.0),
+    );
+    run::ok(
+        "let i = 0; while (i < 5) { i = i + 1; if (i == 2) { break 20; } }",
+        Value::Number(20.0),
+    );
+    run::ok(
+        "let i = 0; while (i < 3) { i = i + 1; if (i == 3) { continue; } i; }",
+        Value::Null,
+    );
+}
+
+#[test]
+fn recursion() {
+    run::ok(
+        "let fib = function(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2); }; fib(15);",
+        Value::Number(610.0),
+    );
+}
+
+#[test]
+fn session() {
+    let mut session = Session::with_backend(Backend::Vm);
+    assert_eq!(
+        session.eval("let counter = function() { let n = 0; function() { n = n + 1; }; };"),
+        Ok(Value::Null)
+    );
+    assert_eq!(
+        session.eval("let c = counter(); c();"),
+        Ok(Value::Number(1.0))
+    );
+    assert_eq!(session.eval("c();"), Ok(Value::Number(2.0)));
+    assert_eq!(session.call("c", vec![]), Ok(Value::Number(3.0)));
+    session.define_native("twice", 1, |args| match &args[0] {
+        Value::Int(n) => Ok(Value::Int(n * 2)),
+        _ => Err("Number expected.".to_string()),
+    });
+    assert_eq!(session.eval("twice(c());"), Ok(Value::Int(8)));
+    assert!(session.get("c").is_some());
+}